@@ -1,11 +1,14 @@
 #ifndef CAMERA_GLSL
 #define CAMERA_GLSL
 
+// Perspective rays start at the origin and go through the view plane, orthographic rays start
+// on the view plane and share the direction, which is zero for perspective
 struct Camera {
     vec3 origin;
     vec3 upper_left_corner;
     vec3 horizontal;
     vec3 vertical;
+    vec3 direction;
 };
 
 #endif
\ No newline at end of file
@@ -0,0 +1,51 @@
+#ifndef CHUNKS_GLSL
+#define CHUNKS_GLSL
+
+#include "world.glsl"
+
+// Expects the world buffer to be declared before the include
+
+float chunk_size() {
+    return float(1 << world.chunk_depth);
+}
+
+ivec3 chunk_coordinate(vec3 pos) {
+    return ivec3(floor(pos / chunk_size()));
+}
+
+// Returns octree index of the layer's chunk at given coordinate or -1 if there is none
+int find_chunk(ivec3 coordinate, uint layer) {
+    for (uint slot = chunk_hash(coordinate, layer); world.chunk_table[slot] != 0; slot = (slot + 1) % CHUNK_TABLE_SIZE) {
+        int chunk = world.chunk_table[slot] - 1;
+
+        if (world.chunk_coordinates[chunk] == ivec4(coordinate, layer))
+            return chunk;
+    }
+
+    return -1;
+}
+
+// Added to world coordinates to get the coordinates inside the chunk's octree
+vec3 chunk_offset(uint chunk) {
+    return world.octrees[chunk].bounds.lower - vec3(world.chunk_coordinates[chunk].xyz) * chunk_size();
+}
+
+// Slot holding the color of the layer's voxel, or the empty slot where it would go
+uint color_slot(vec3 pos, uint layer) {
+    ivec4 key = ivec4(ivec3(pos), layer);
+    uint slot = color_hash(key.xyz, layer);
+
+    while (world.colors[slot] != 0 && world.color_voxels[slot] != key)
+        slot = (slot + 1) % COLOR_TABLE_SIZE;
+
+    return slot;
+}
+
+// Voxels which weren't painted have the default color
+vec3 voxel_color(vec3 pos, uint layer) {
+    uint color = world.colors[color_slot(pos, layer)];
+
+    return unpack_color(color != 0 ? color : DEFAULT_COLOR);
+}
+
+#endif
\ No newline at end of file
@@ -4,6 +4,8 @@
 struct Cursor {
     vec3 pos;
     ivec3 normal;
+    vec3 local_pos; // Voxel of the instance's model, counted from the model's lower corner
+    int instance; // -1 unless the cursor is on an instance
 };
 
 #endif
\ No newline at end of file
@@ -1,10 +1,18 @@
 #ifndef MOUSE_GLSL
 #define MOUSE_GLSL
 
+// Tools decide what the left button does, the right button always erases
+#define TOOL_PENCIL 0
+#define TOOL_ERASER 1
+#define TOOL_PAINT 2
+#define TOOL_EYEDROPPER 3 // Picks the color on the CPU, so the shaders leave the world alone
+
 struct Mouse {
     vec2 coordinate;
     bool left_button;
     bool right_button;
+    uint tool;
+    uint color; // 0xRRGGBB the pencil and paint tools use
 };
 
 #endif
\ No newline at end of file
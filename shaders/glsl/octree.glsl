@@ -4,14 +4,15 @@
 #define MEMORY_SIZE 1024
 
 struct Bounds {
-    vec3 upper;
     vec3 lower;
+    vec3 upper;
 };
 
 struct Octree {
     Bounds bounds;
     uint descriptors[MEMORY_SIZE];
     uint free_address;
+    uint depth;
 };
 
 #endif
\ No newline at end of file
@@ -9,9 +9,14 @@ struct Ray {
 };
 
 Ray create_ray(Camera camera, float u, float v) {
+    vec3 view_plane_pos = camera.upper_left_corner + u * camera.horizontal - v * camera.vertical;
+
+    if (camera.direction != vec3(0.0))
+        return Ray(view_plane_pos, camera.direction);
+
     Ray ray = Ray(
         camera.origin, 
-        camera.upper_left_corner + u * camera.horizontal - v * camera.vertical - camera.origin
+        view_plane_pos - camera.origin
     );
 
     return ray;
@@ -0,0 +1,379 @@
+#ifndef RAYMARCH_GLSL
+#define RAYMARCH_GLSL
+
+#include "chunks.glsl"
+
+// Expects the world buffer to be declared before the include
+
+#define STACK_SIZE 23
+#define EPS 1.1920929e-7 // 2^(-23)
+
+struct StackItem {
+	uint node;
+	float t_max;
+} stack[STACK_SIZE];
+
+#define NO_HIT 3.402823e38 // Ray parameter of a miss, nothing can be further away
+#define BVH_STACK_SIZE 32
+
+struct Hit {
+    vec3 pos;
+    ivec3 normal;
+    float t;
+    vec3 local_pos; // Voxel of the instance's model, counted from the model's lower corner
+    int instance; // -1 for voxels of the chunks
+    uint layer; // Layer of the chunk's voxel
+};
+
+// Ray and hit position are in the coordinates of the octree, hit_t is the ray parameter at the hit
+bool octree_raymarch(uint octree, vec3 o, vec3 d, out vec3 hit_pos, out ivec3 hit_normal, out float hit_t) {
+    // Octree resides at coordinates [2^depth, 2^(depth + 1)] ([1, 2] * 2^depth)
+    float lower_bound = world.octrees[octree].bounds.lower.x;
+    float upper_bound = lower_bound * 2.0;
+    float mid_point = (lower_bound + upper_bound) * 0.5;
+    float mirror = lower_bound + upper_bound;
+
+    // Get rid of small ray direction components to avoid division by zero
+    d.x = abs(d.x) > EPS ? d.x : (d.x >= 0 ? EPS : -EPS);
+	d.y = abs(d.y) > EPS ? d.y : (d.y >= 0 ? EPS : -EPS);
+	d.z = abs(d.z) > EPS ? d.z : (d.z >= 0 ? EPS : -EPS);
+
+    // Precompute the coefficients of tx(x), ty(y), and tz(z)
+    vec3 t_coef = 1.0 / -abs(d);
+	vec3 t_bias = t_coef * o;
+
+    // Select octant mask to mirror the coordinate system so
+    // that ray direction is negative along each axis.
+    uint oct_mask = 0;
+    ivec3 d_rev = ivec3(1); // Reversed ray direction, needed later for finding the hit side
+    
+    if (d.x > 0.0)
+		oct_mask ^= 1, d_rev.x = -1, t_bias.x = mirror * t_coef.x - t_bias.x;
+	if (d.y > 0.0)
+		oct_mask ^= 2, d_rev.y = -1, t_bias.y = mirror * t_coef.y - t_bias.y;
+	if (d.z > 0.0)
+		oct_mask ^= 4, d_rev.z = -1, t_bias.z = mirror * t_coef.z - t_bias.z;
+    
+    // Initialize the active span of t-values.
+	float t_min = max(max(upper_bound * t_coef.x - t_bias.x, upper_bound * t_coef.y - t_bias.y), upper_bound * t_coef.z - t_bias.z);
+	float t_max = min(min(lower_bound * t_coef.x - t_bias.x, lower_bound * t_coef.y - t_bias.y), lower_bound * t_coef.z - t_bias.z);
+    // Negative t_min means we start inside the root I think
+    t_min = max(t_min, 0.0);
+    float h = t_max;
+    
+    // If the ray missed the root
+    if (t_min > t_max)  
+        return false;
+
+    // Root is located at the last location in WorldBuffer
+    uint parent = world.octrees[octree].descriptors[MEMORY_SIZE - 1];
+    // Cached child descriptor
+    uint descriptor = 0;
+    vec3 pos = vec3(lower_bound);
+    uint idx = 0;
+
+    if (mid_point * t_coef.x - t_bias.x > t_min)
+		idx ^= 1, pos.x = mid_point;
+	if (mid_point * t_coef.y - t_bias.y > t_min)
+		idx ^= 2, pos.y = mid_point;
+	if (mid_point * t_coef.z - t_bias.z > t_min)
+		idx ^= 4, pos.z = mid_point;
+
+    uint scale = STACK_SIZE - 1;
+    uint leaf_scale = STACK_SIZE - world.octrees[octree].depth;
+    float scale_exp2 = 0.5 * lower_bound;
+
+    while (scale < STACK_SIZE) {
+        if (descriptor == 0)
+            descriptor = world.octrees[octree].descriptors[(parent >> 8) + (idx ^ oct_mask)];
+
+        // Determine maximum t-value of the cube by evaluating tx(), ty(), and tz() at its corner
+        vec3 t_corner = pos * t_coef - t_bias;
+        float tc_max = min(min(t_corner.x, t_corner.y), t_corner.z);
+
+        if ((parent & 1 << (idx ^ oct_mask)) != 0 && t_min <= t_max) {
+            // INTERSECT
+            float tv_max = min(t_max, tc_max);
+            float half_scale_exp2 = scale_exp2 * 0.5;
+            // Intersect active t-span with the cube and evaluate tx(), ty(), and tz() at the center of the voxel
+            vec3 t_center = half_scale_exp2 * t_coef + t_corner;
+
+            if (t_min <= tv_max) {
+                // If ray has hit a leaf child
+                if (scale == leaf_scale) {
+                    // Undo the mirroring of the coordinate system
+                    if ((oct_mask & 1) != 0)
+		                pos.x = ((upper_bound - 1.0) - pos.x) + lower_bound;
+	                if ((oct_mask & 2) != 0)
+	                	pos.y = ((upper_bound - 1.0) - pos.y) + lower_bound;
+	                if ((oct_mask & 4) != 0)
+	                	pos.z = ((upper_bound - 1.0) - pos.z) + lower_bound;
+
+                    // Determine which side is hit
+                    vec3 t_opp_corner = scale_exp2 * t_coef + t_corner;
+                    ivec3 normal = ivec3(0);
+
+                    if (t_opp_corner.x > t_opp_corner.y && t_opp_corner.x > t_opp_corner.z) 
+                        normal.x = d_rev.x; 
+                    else if (t_opp_corner.y > t_opp_corner.z) 
+                        normal.y = d_rev.y; 
+                    else 
+                        normal.z = d_rev.z;
+
+                    hit_pos = pos;
+                    hit_normal = normal;
+                    hit_t = t_min;
+
+                    return true;
+                }
+
+                // PUSH
+                if (tc_max < h)
+                    stack[scale] = StackItem(parent, t_max);
+
+                h = tc_max;
+                parent = descriptor;
+
+                // Select child voxel that the ray enters first
+                idx = 0;
+                scale -= 1;
+                scale_exp2 = half_scale_exp2;
+
+                if (t_center.x > t_min)
+    				idx ^= 1, pos.x += scale_exp2;
+    			if (t_center.y > t_min)
+    				idx ^= 2, pos.y += scale_exp2;
+    			if (t_center.z > t_min)
+    				idx ^= 4, pos.z += scale_exp2;
+
+                descriptor = 0;
+                t_max = tv_max;
+
+                continue;
+            }
+        }
+
+        // ADVANCE
+        uint step_mask = 0;
+
+		if (t_corner.x <= tc_max)
+			step_mask ^= 1, pos.x -= scale_exp2;
+		if (t_corner.y <= tc_max)
+			step_mask ^= 2, pos.y -= scale_exp2;
+		if (t_corner.z <= tc_max)
+			step_mask ^= 4, pos.z -= scale_exp2;
+
+        // Update active t-span and flip bits of the child slot index
+        t_min = tc_max;
+        idx ^= step_mask;
+
+        // If the ray exists the octree without hitting a leaf voxel
+        if (pos.x < lower_bound || pos.y < lower_bound || pos.z < lower_bound)
+            return false;
+
+        // Proceed with pop if the bit flips disagree with the ray direction
+        if ((idx & step_mask) != 0) {
+            // POP
+			// Find the highest differing bit between two positions
+            uint differing_bits = 0;
+
+			if ((step_mask & 1) != 0)
+				differing_bits |= floatBitsToUint(pos.x) ^ floatBitsToUint(pos.x + scale_exp2);
+			if ((step_mask & 2) != 0)
+				differing_bits |= floatBitsToUint(pos.y) ^ floatBitsToUint(pos.y + scale_exp2);
+			if ((step_mask & 4) != 0)
+				differing_bits |= floatBitsToUint(pos.z) ^ floatBitsToUint(pos.z + scale_exp2);
+
+            // Find MSB
+            scale = findMSB(differing_bits);
+            scale_exp2 = uintBitsToFloat((scale - STACK_SIZE + 127) << 23) * lower_bound; // exp2f(scale - s_max) * lower_bound
+
+            // Restore parent voxel from the stack
+            parent = stack[scale].node;
+			t_max = stack[scale].t_max;
+
+            // Round cube position and extract child slot index
+            uint shx = floatBitsToUint(pos.x) >> scale;
+			uint shy = floatBitsToUint(pos.y) >> scale;
+			uint shz = floatBitsToUint(pos.z) >> scale;
+			pos.x = uintBitsToFloat(shx << scale);
+			pos.y = uintBitsToFloat(shy << scale);
+			pos.z = uintBitsToFloat(shz << scale);
+
+            idx = (shx & 1) | ((shy & 1) << 1) | ((shz & 1) << 2);
+
+            // Prevent same parent from being stored again and invalidate cached child descriptor
+            h = 0.0;
+			descriptor = 0;
+        } 
+    }
+    return false;
+}
+
+// Walks through the chunks along the ray with a 3D DDA and traverses octrees of those that exist.
+// Chunks of a layer don't overlap, so the nearest hit among the visible layers in the first
+// cell with any hit is the nearest one.
+bool world_raymarch(vec3 o, vec3 d, out Hit hit) {
+    hit.t = NO_HIT;
+
+    if (world.chunk_count == 0)
+        return false;
+
+    float size = chunk_size();
+    ivec3 lower_chunk = world.lower_chunk.xyz;
+    ivec3 upper_chunk = world.upper_chunk.xyz;
+
+    // Get rid of small ray direction components to avoid division by zero
+    d.x = abs(d.x) > EPS ? d.x : (d.x >= 0 ? EPS : -EPS);
+	d.y = abs(d.y) > EPS ? d.y : (d.y >= 0 ? EPS : -EPS);
+	d.z = abs(d.z) > EPS ? d.z : (d.z >= 0 ? EPS : -EPS);
+
+    // Clip the ray with the box around all chunks
+    vec3 t_lower = (vec3(lower_chunk) * size - o) / d;
+    vec3 t_upper = (vec3(upper_chunk + 1) * size - o) / d;
+    vec3 t_near = min(t_lower, t_upper);
+    vec3 t_far = max(t_lower, t_upper);
+    float t_min = max(max(max(t_near.x, t_near.y), t_near.z), 0.0);
+    float t_max = min(min(t_far.x, t_far.y), t_far.z);
+
+    if (t_min > t_max)
+        return false;
+
+    ivec3 cell = clamp(ivec3(floor((o + t_min * d) / size)), lower_chunk, upper_chunk);
+    ivec3 cell_step = ivec3(sign(d));
+    vec3 t_delta = abs(size / d);
+    vec3 t_next = (vec3(cell + max(cell_step, 0)) * size - o) / d;
+
+    while (all(greaterThanEqual(cell, lower_chunk)) && all(lessThanEqual(cell, upper_chunk))) {
+        for (uint layer = 0; layer < world.layer_count; ++layer) {
+            int chunk = (world.visible_layers & 1u << layer) != 0 ? find_chunk(cell, layer) : -1;
+
+            if (chunk < 0)
+                continue;
+
+            vec3 offset = chunk_offset(chunk);
+            vec3 pos;
+            ivec3 normal;
+            float t;
+
+            // Chunk octrees are only moved, so the ray parameter stays the same
+            if (octree_raymarch(chunk, o + offset, d, pos, normal, t) && t < hit.t) {
+                hit.pos = pos - offset;
+                hit.normal = normal;
+                hit.t = t;
+                hit.local_pos = hit.pos;
+                hit.instance = -1;
+                hit.layer = layer;
+            }
+        }
+
+        if (hit.t != NO_HIT)
+            return true;
+
+        // Step into the neighbour across the nearest chunk side
+        if (t_next.x < t_next.y && t_next.x < t_next.z)
+            cell.x += cell_step.x, t_next.x += t_delta.x;
+        else if (t_next.y < t_next.z)
+            cell.y += cell_step.y, t_next.y += t_delta.y;
+        else
+            cell.z += cell_step.z, t_next.z += t_delta.z;
+    }
+
+    return false;
+}
+
+bool box_raymarch(vec3 lower, vec3 upper, vec3 o, vec3 d, float t_max) {
+    vec3 t_lower = (lower - o) / d;
+    vec3 t_upper = (upper - o) / d;
+    vec3 t_near = min(t_lower, t_upper);
+    vec3 t_far = max(t_lower, t_upper);
+    float t_min = max(max(max(t_near.x, t_near.y), t_near.z), 0.0);
+
+    return t_min <= min(min(min(t_far.x, t_far.y), t_far.z), t_max);
+}
+
+// Traverses the model of the instance in model coordinates, the hit replaces the given one only if it's nearer
+bool instance_raymarch(uint index, vec3 o, vec3 d, inout Hit hit) {
+    Instance instance = world.instances[index];
+    ivec4 rows[3] = instance.rotation;
+    // Rotations are orthogonal, so the inverse is the transpose and columns of to_model are rows of the rotation
+    mat3 to_model = mat3(vec3(rows[0].xyz), vec3(rows[1].xyz), vec3(rows[2].xyz));
+    mat3 to_world = transpose(to_model);
+    vec3 model_center = instance.model_center.xyz;
+    vec3 world_center = instance.world_center.xyz;
+
+    vec3 pos;
+    ivec3 normal;
+    float t;
+
+    // Rotation keeps lengths, so the ray parameter is the same in both coordinate systems
+    if (!octree_raymarch(instance.octree, to_model * (o - world_center) + model_center, to_model * d, pos, normal, t) || t >= hit.t)
+        return false;
+
+    // Rotate the center of the voxel, its lower corner depends on the rotation
+    hit.pos = floor(to_world * (pos + 0.5 - model_center) + world_center);
+    hit.normal = ivec3(to_world * vec3(normal));
+    hit.t = t;
+    hit.local_pos = pos - world.octrees[instance.octree].bounds.lower;
+    hit.instance = int(index);
+
+    return true;
+}
+
+// Finds the nearest instance hit by the ray with the bounding volume hierarchy,
+// skipping everything further away than the given hit
+bool scene_raymarch(vec3 o, vec3 d, inout Hit hit) {
+    if (world.instance_count == 0)
+        return false;
+
+    // Get rid of small ray direction components to avoid division by zero
+    d.x = abs(d.x) > EPS ? d.x : (d.x >= 0 ? EPS : -EPS);
+	d.y = abs(d.y) > EPS ? d.y : (d.y >= 0 ? EPS : -EPS);
+	d.z = abs(d.z) > EPS ? d.z : (d.z >= 0 ? EPS : -EPS);
+
+    int nodes[BVH_STACK_SIZE];
+    int node_count = 1;
+    bool found = false;
+
+    nodes[0] = 0;
+
+    while (node_count > 0) {
+        BvhNode node = world.bvh[nodes[--node_count]];
+
+        if (!box_raymarch(node.lower.xyz, node.upper.xyz, o, d, hit.t))
+            continue;
+
+        if (node.instance >= 0) {
+            found = instance_raymarch(uint(node.instance), o, d, hit) || found;
+        } else {
+            nodes[node_count++] = node.left;
+            nodes[node_count++] = node.right;
+        }
+    }
+
+    return found;
+}
+
+// Nearest voxel of either the chunks or the instances
+bool raymarch(vec3 o, vec3 d, out Hit hit) {
+    bool found = world_raymarch(o, d, hit);
+
+    return scene_raymarch(o, d, hit) || found;
+}
+
+// Ground lies under the lowest chunks and lets voxels be placed where there is nothing to build on yet.
+// Hit position is the voxel right under the ground, like the voxel hit by the ray would be.
+bool ground_raymarch(vec3 o, vec3 d, out vec3 hit_pos, out vec3 hit_coord, out float hit_t) {
+    float ground = world.chunk_count == 0 ? 0.0 : float(world.lower_chunk.z) * chunk_size();
+
+    if (d.z >= 0.0 || o.z <= ground)
+        return false;
+
+    hit_t = (ground - o.z) / d.z;
+    hit_coord = o + hit_t * d;
+    hit_pos = vec3(floor(hit_coord.x), floor(hit_coord.y), ground - 1.0);
+
+    return true;
+}
+
+#endif
\ No newline at end of file
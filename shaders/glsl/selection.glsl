@@ -0,0 +1,11 @@
+#ifndef SELECTION_GLSL
+#define SELECTION_GLSL
+
+// Box of voxels, both corners are inclusive
+struct Selection {
+    ivec3 lower;
+    ivec3 upper;
+    bool enabled; // False when nothing is selected
+};
+
+#endif
\ No newline at end of file
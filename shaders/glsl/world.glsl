@@ -0,0 +1,75 @@
+#ifndef WORLD_GLSL
+#define WORLD_GLSL
+
+#include "octree.glsl"
+
+#define MAX_OCTREES 64
+#define MAX_INSTANCES 64
+#define MAX_LAYERS 8
+#define CHUNK_TABLE_SIZE (2 * MAX_OCTREES)
+#define MAX_COLORS 4096
+#define COLOR_TABLE_SIZE (2 * MAX_COLORS)
+#define DEFAULT_COLOR 0x33FFFF // Color of voxels which weren't painted
+#define COLOR_USED_BIT (1u << 24)
+
+// Places a model octree into the world rotated by multiples of 90 degrees around its center
+struct Instance {
+    ivec4 rotation[3]; // Rows of the rotation from model to world coordinates
+    vec4 model_center;
+    vec4 world_center;
+    uint octree;
+};
+
+// Node of the bounding volume hierarchy over instances, the root is the first node
+struct BvhNode {
+    vec4 lower;
+    vec4 upper;
+    int left; // Child nodes, -1 for leaves
+    int right;
+    int instance; // Instance of a leaf, -1 for inner nodes
+};
+
+// World is made of chunk octrees of the same depth which are created on demand.
+// Chunk with coordinate c covers [c, c + 1) * chunk size in world coordinates.
+// Every layer has chunks of its own, so layers can overlap and be shown or locked separately.
+// Octrees of the chunks and of the models placed by instances share the same pool. Chunks take
+// the octrees at its start in the order they were created and models take the ones at its end.
+struct World {
+    Octree octrees[MAX_OCTREES];
+    ivec4 chunk_coordinates[MAX_OCTREES]; // Layer of the chunk is kept in w
+    int chunk_table[CHUNK_TABLE_SIZE]; // Octree index + 1 found by linear probing, 0 marks an empty slot
+    ivec4 lower_chunk; // Inclusive range of chunk coordinates in use
+    ivec4 upper_chunk;
+    Instance instances[MAX_INSTANCES];
+    BvhNode bvh[2 * MAX_INSTANCES];
+    ivec4 color_voxels[COLOR_TABLE_SIZE]; // Painted voxels of the chunks with their layer in w
+    uint colors[COLOR_TABLE_SIZE]; // 0xRRGGBB | COLOR_USED_BIT found by linear probing, 0 marks an empty slot
+    uint octree_count;
+    uint chunk_count;
+    uint chunk_depth;
+    uint instance_count;
+    uint layer_count;
+    uint active_layer; // Layer which receives the edits
+    uint visible_layers; // Bit for every layer
+    uint locked_layers;
+    uint color_count;
+};
+
+uint chunk_hash(ivec3 coordinate, uint layer) {
+    uint hash = uint(coordinate.x) * 73856093u ^ uint(coordinate.y) * 19349663u ^ uint(coordinate.z) * 83492791u ^ layer * 50331653u;
+
+    return hash % CHUNK_TABLE_SIZE;
+}
+
+uint color_hash(ivec3 pos, uint layer) {
+    uint hash = uint(pos.x) * 73856093u ^ uint(pos.y) * 19349663u ^ uint(pos.z) * 83492791u ^ layer * 50331653u;
+
+    return hash % COLOR_TABLE_SIZE;
+}
+
+// Color as 0xRRGGBB to the 0-255 components shading works with
+vec3 unpack_color(uint color) {
+    return vec3(color >> 16 & 0xFF, color >> 8 & 0xFF, color & 0xFF);
+}
+
+#endif
\ No newline at end of file
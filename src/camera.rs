@@ -1,98 +1,620 @@
+use std::f32::consts::{
+    FRAC_PI_2,
+    FRAC_PI_4,
+    FRAC_1_SQRT_2,
+    PI
+};
 use nalgebra::{
-    Vector3, 
+    Vector3,
     Vector4
 };
 use winit::event::{
-    VirtualKeyCode, 
-    ElementState
+    ElementState,
+    ModifiersState
 };
+use super::input::Action;
+
+const MOVE_SPEED: f32 = 8.0; // Voxels per second
+const ACCELERATION: f32 = 8.0; // How fast the velocity reaches the speed of the held keys, per second
+const FAST_SPEED: f32 = 4.0; // Multiplies the speed while shift is held
+const SLOW_SPEED: f32 = 0.25; // Multiplies the speed while ctrl is held
+const MIN_VELOCITY: f32 = 0.01; // Slower camera without held keys stops
+const MAX_DELTA_TIME: f32 = 0.1; // Long frames like after a minimized window don't make the camera jump
+const ROTATE_SPEED: f32 = 0.005; // Radians per pixel dragged
+const PAN_SPEED: f32 = 0.002; // Part of the distance to the pivot or of the orthographic view per pixel dragged
+const ZOOM_STEP: f32 = 0.9; // Distance to the pivot is multiplied by it for every scroll line
+const MIN_DISTANCE: f32 = 1.0;
+const MIN_VIEW_HEIGHT: f32 = 1.0; // Orthographic view is at least a voxel high
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01; // Keeps the view direction away from vector up
 
 #[repr(C)]
 pub struct CameraProjection {
-    origin: Vector4<f32>,
-    upper_left_corner: Vector4<f32>,
-    horizontal: Vector4<f32>,
-    vertical: Vector4<f32>,
+    pub origin: Vector4<f32>,
+    pub upper_left_corner: Vector4<f32>,
+    pub horizontal: Vector4<f32>,
+    pub vertical: Vector4<f32>,
+    pub direction: Vector4<f32>, // Shared by all rays of an orthographic projection, zero for perspective
+}
+
+/// Orbit rotates the camera around look at as the pivot, fly turns look at around the camera
+#[derive(Clone, Copy, PartialEq)]
+pub enum CameraMode {
+    Orbit,
+    Fly,
+}
+
+impl CameraMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CameraMode::Orbit => "orbit",
+            CameraMode::Fly => "fly",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective,
+    Orthographic,
 }
 
+impl Projection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Projection::Perspective => "perspective",
+            Projection::Orthographic => "orthographic",
+        }
+    }
+}
+
+/// Axis-aligned views and the isometric one, looking at the center of the scene
+#[derive(Clone, Copy, PartialEq)]
+pub enum PresetView {
+    Front,
+    Back,
+    Left,
+    Right,
+    Top,
+    Bottom,
+    Isometric,
+}
+
+impl PresetView {
+    // Yaw and pitch of the view direction, top and bottom keep the back of the scene up
+    fn angles(&self) -> (f32, f32) {
+        match self {
+            PresetView::Front => (FRAC_PI_2, 0.0),
+            PresetView::Back => (-FRAC_PI_2, 0.0),
+            PresetView::Left => (0.0, 0.0),
+            PresetView::Right => (PI, 0.0),
+            PresetView::Top => (FRAC_PI_2, -MAX_PITCH),
+            PresetView::Bottom => (FRAC_PI_2, MAX_PITCH),
+            PresetView::Isometric => (FRAC_PI_4, -FRAC_1_SQRT_2.atan()),
+        }
+    }
+}
+
+/// Named camera position saved in the scene file
+#[derive(Clone)]
+pub struct Bookmark {
+    pub name: String,
+    pub look_from: Vector3<f32>,
+    pub look_at: Vector3<f32>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Drag {
+    Rotate, // Alt and left button
+    Pan, // Middle button
+}
+
+#[derive(Clone)]
 pub struct CameraController {
-    delta_move: Vector3<f32>,
+    positive_keys: Vector3<f32>, // 1 for every held key moving along right, forward and up of the view
+    negative_keys: Vector3<f32>, // 1 for every held key moving the opposite way
+    velocity: Vector3<f32>, // Along right, forward and up of the view in voxels per second
+    speed: f32, // Set by the modifiers
+    drag: Option<Drag>,
 }
 
 impl Default for CameraController {
     fn default() -> Self {
-        Self { 
-            delta_move: Default::default(), 
+        Self {
+            positive_keys: Default::default(),
+            negative_keys: Default::default(),
+            velocity: Default::default(),
+            speed: 1.0,
+            drag: None,
         }
     }
 }
 
+#[derive(Clone)]
 pub struct Camera {
-    look_from: Vector3<f32>, 
-    look_at: Vector3<f32>, 
-    vector_up: Vector3<f32>, 
-    field_of_view: f32, 
+    look_from: Vector3<f32>,
+    look_at: Vector3<f32>,
+    vector_up: Vector3<f32>,
+    field_of_view: f32,
     aspect_ratio: f32,
+    mode: CameraMode,
+    projection: Projection,
+    view_height: f32, // Height of the orthographic view in voxels
     controller: CameraController,
 }
 
 impl Camera {
     pub fn new(look_from: Vector3<f32>, look_at: Vector3<f32>) -> Self {
-        Self { 
-            look_from, 
-            look_at, 
-            vector_up: Vector3::new(0.0, 0.0, 1.0), 
-            field_of_view: 45.0, 
-            aspect_ratio: 16.0 / 9.0, 
+        Self {
+            look_from,
+            look_at,
+            vector_up: Vector3::new(0.0, 0.0, 1.0),
+            field_of_view: 45.0,
+            aspect_ratio: 16.0 / 9.0,
+            mode: CameraMode::Orbit,
+            projection: Projection::Perspective,
+            view_height: 0.0,
             controller: CameraController::default(),
         }
     }
 
-    pub fn projection(&mut self) -> CameraProjection {
-        self.look_from += self.controller.delta_move;
+    pub fn set_field_of_view(&mut self, field_of_view: f32) {
+        self.field_of_view = field_of_view;
+    }
 
-        let theta = self.field_of_view.to_radians();
-        let h = (theta / 2.0).tan();
-        let viewport_height = 2.0 * h;
-        let viewport_width = self.aspect_ratio * viewport_height;
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        self.aspect_ratio = aspect_ratio;
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
 
-        let w = (self.look_at - self.look_from).normalize();
-        let u = w.cross(&self.vector_up).normalize();
-        let v = u.cross(&w);
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            CameraMode::Orbit => CameraMode::Fly,
+            CameraMode::Fly => CameraMode::Orbit,
+        };
+    }
+
+    pub fn projection_mode(&self) -> Projection {
+        self.projection
+    }
+
+    /// Orthographic view starts with the size the perspective view has at look at
+    pub fn toggle_projection(&mut self) {
+        self.projection = match self.projection {
+            Projection::Perspective => {
+                self.view_height = 2.0 * (self.field_of_view.to_radians() / 2.0).tan() * self.distance();
+                Projection::Orthographic
+            },
+            Projection::Orthographic => Projection::Perspective,
+        };
+    }
+
+    pub fn bookmark(&self, name: &str) -> Bookmark {
+        Bookmark {
+            name: String::from(name),
+            look_from: self.look_from,
+            look_at: self.look_at,
+        }
+    }
+
+    pub fn go_to(&mut self, bookmark: &Bookmark) {
+        self.look_from = bookmark.look_from;
+        self.look_at = bookmark.look_at;
+    }
+
+    /// Copy of the camera at the bookmark, which the controls don't move
+    pub fn at(&self, bookmark: &Bookmark) -> Self {
+        let mut camera = self.clone();
+        camera.controller = CameraController::default();
+        camera.go_to(bookmark);
+
+        camera
+    }
+
+    /// Looks at the center of the box from far enough to see all of it
+    pub fn frame(&mut self, view: PresetView, lower: Vector3<f32>, upper: Vector3<f32>) {
+        let (yaw, pitch) = view.angles();
+        let forward = Vector3::new(yaw.cos() * pitch.cos(), yaw.sin() * pitch.cos(), pitch.sin());
+        let radius = (upper - lower).norm() * 0.5; // Of a sphere around the box
+
+        // Sphere fits vertically, which is the narrower side of a landscape window
+        let distance = radius / (self.field_of_view.to_radians() / 2.0).sin();
+
+        self.look_at = (lower + upper) * 0.5;
+        self.look_from = self.look_at - forward * distance;
+        self.view_height = 2.0 * radius;
+    }
+
+    /// Moves the camera by the time in seconds since the last update
+    pub fn update(&mut self, delta_time: f32) {
+        let delta_time = delta_time.min(MAX_DELTA_TIME);
+        let controller = &mut self.controller;
+        let direction = controller.positive_keys - controller.negative_keys;
+
+        let target_velocity = match direction == Vector3::zeros() {
+            true => Vector3::zeros(),
+            false => direction.normalize() * MOVE_SPEED * controller.speed,
+        };
+
+        // Velocity approaches the target exponentially, so the camera speeds up and stops smoothly at any frame rate
+        controller.velocity += (target_velocity - controller.velocity) * (1.0 - (-ACCELERATION * delta_time).exp());
+
+        if target_velocity == Vector3::zeros() && controller.velocity.norm() < MIN_VELOCITY {
+            controller.velocity = Vector3::zeros();
+        }
+
+        let (right, forward, up) = self.axes();
+        let velocity = self.controller.velocity;
+        let translation = (velocity.x * right + velocity.y * forward + velocity.z * up) * delta_time;
+
+        // Both ends move together, so the view direction stays the same
+        self.look_from += translation;
+        self.look_at += translation;
+    }
+
+    pub fn projection(&self) -> CameraProjection {
+        let (right, forward, up) = self.axes();
+
+        // Perspective view plane is one unit in front of the camera, orthographic goes through it
+        let (viewport_height, view_plane_distance, direction) = match self.projection {
+            Projection::Perspective => (2.0 * (self.field_of_view.to_radians() / 2.0).tan(), 1.0, Vector3::zeros()),
+            Projection::Orthographic => (self.view_height, 0.0, forward),
+        };
+        let viewport_width = self.aspect_ratio * viewport_height;
 
         let origin = self.look_from.to_homogeneous();
-        let horizontal = (viewport_width * u).to_homogeneous();
-        let vertical = (viewport_height * v).to_homogeneous();
-        let upper_left_corner = origin - horizontal * 0.5 + vertical * 0.5 + w.to_homogeneous();
+        let horizontal = (viewport_width * right).to_homogeneous();
+        let vertical = (viewport_height * up).to_homogeneous();
+        let upper_left_corner = origin - horizontal * 0.5 + vertical * 0.5 + (forward * view_plane_distance).to_homogeneous();
 
         CameraProjection {
             origin,
             upper_left_corner,
             horizontal,
             vertical,
+            direction: direction.to_homogeneous(),
         }
     }
 
-    pub fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) {
-        let delta = match state {
-            ElementState::Pressed => 0.01,
+    /// Move actions move along the view direction while held, rotate and pan actions drag the camera
+    pub fn process_action(&mut self, action: Action, state: ElementState) {
+        let held = match state {
+            ElementState::Pressed => 1.0,
             ElementState::Released => 0.0,
         };
 
-        match key {
-            VirtualKeyCode::W => {
-                self.controller.delta_move.z = delta;
+        match action {
+            Action::MoveForward => {
+                self.controller.positive_keys.y = held;
+            },
+            Action::MoveBackward => {
+                self.controller.negative_keys.y = held;
+            },
+            Action::MoveRight => {
+                self.controller.positive_keys.x = held;
             },
-            VirtualKeyCode::S => {
-                self.controller.delta_move.z = -delta;
+            Action::MoveLeft => {
+                self.controller.negative_keys.x = held;
             },
-            VirtualKeyCode::D => {
-                self.controller.delta_move.x = delta;
+            Action::MoveUp => {
+                self.controller.positive_keys.z = held;
             },
-            VirtualKeyCode::A => {
-                self.controller.delta_move.x = -delta;
+            Action::MoveDown => {
+                self.controller.negative_keys.z = held;
             },
+            Action::RotateCamera => self.drag(Drag::Rotate, state),
+            Action::PanCamera => self.drag(Drag::Pan, state),
             _ => (),
         }
     }
+
+    /// Shift moves faster and ctrl slower
+    pub fn process_modifiers(&mut self, modifiers: ModifiersState) {
+        self.controller.speed = match (modifiers.shift(), modifiers.ctrl()) {
+            (true, _) => FAST_SPEED,
+            (false, true) => SLOW_SPEED,
+            (false, false) => 1.0,
+        };
+    }
+
+    /// Rotates or pans by the distance the mouse moved in pixels while dragging
+    pub fn process_mouse_motion(&mut self, delta_x: f32, delta_y: f32) {
+        match self.controller.drag {
+            Some(Drag::Rotate) => self.rotate(-delta_x * ROTATE_SPEED, -delta_y * ROTATE_SPEED),
+            Some(Drag::Pan) => {
+                let (right, _, up) = self.axes();
+                let scale = PAN_SPEED * match self.projection {
+                    Projection::Perspective => self.distance(),
+                    Projection::Orthographic => self.view_height,
+                };
+                let translation = (-delta_x * right + delta_y * up) * scale;
+
+                self.look_from += translation;
+                self.look_at += translation;
+            },
+            None => (),
+        }
+    }
+
+    /// Orbit zooms towards the pivot, fly moves forward and orthographic projection scales the view in both
+    pub fn process_scroll(&mut self, lines: f32) {
+        let (_, forward, _) = self.axes();
+
+        if self.projection == Projection::Orthographic {
+            self.view_height = (self.view_height * ZOOM_STEP.powf(lines)).max(MIN_VIEW_HEIGHT);
+            return;
+        }
+
+        match self.mode {
+            CameraMode::Orbit => {
+                let distance = (self.distance() * ZOOM_STEP.powf(lines)).max(MIN_DISTANCE);
+                self.look_from = self.look_at - forward * distance;
+            },
+            CameraMode::Fly => {
+                self.look_from += forward * lines;
+                self.look_at += forward * lines;
+            },
+        }
+    }
+
+    // Releasing ends only the drag it started
+    fn drag(&mut self, drag: Drag, state: ElementState) {
+        match state {
+            ElementState::Pressed => self.controller.drag = Some(drag),
+            ElementState::Released if self.controller.drag == Some(drag) => self.controller.drag = None,
+            ElementState::Released => (),
+        }
+    }
+
+    // Turns the view direction by yaw around vector up and by pitch up or down
+    fn rotate(&mut self, yaw: f32, pitch: f32) {
+        let (_, forward, _) = self.axes();
+        let distance = self.distance();
+
+        let yaw = forward.y.atan2(forward.x) + yaw;
+        let pitch = (forward.z.clamp(-1.0, 1.0).asin() + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+        let forward = Vector3::new(yaw.cos() * pitch.cos(), yaw.sin() * pitch.cos(), pitch.sin());
+
+        match self.mode {
+            CameraMode::Orbit => self.look_from = self.look_at - forward * distance,
+            CameraMode::Fly => self.look_at = self.look_from + forward * distance,
+        }
+    }
+
+    // Right, forward and up directions of the view, which looks along y when both ends are the same point and has
+    // the right of the top and bottom preset views when it looks straight along vector up
+    fn axes(&self) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>) {
+        let forward = (self.look_at - self.look_from).try_normalize(f32::EPSILON).unwrap_or_else(Vector3::y);
+        let right = forward.cross(&self.vector_up).try_normalize(f32::EPSILON).unwrap_or_else(Vector3::x);
+        let up = right.cross(&forward);
+
+        (right, forward, up)
+    }
+
+    fn distance(&self) -> f32 {
+        (self.look_at - self.look_from).norm()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_finite_basis(camera: &Camera) {
+        let (right, forward, up) = camera.axes();
+
+        for axis in [right, forward, up] {
+            assert!(axis.iter().all(|value| value.is_finite()), "{:?}", axis);
+            assert!((axis.norm() - 1.0).abs() < 1e-4, "{:?}", axis);
+        }
+        assert!(right.dot(&forward).abs() < 1e-4 && up.dot(&forward).abs() < 1e-4);
+
+        let projection = camera.projection();
+        for vector in [projection.origin, projection.upper_left_corner, projection.horizontal, projection.vertical] {
+            assert!(vector.iter().all(|value| value.is_finite()), "{:?}", vector);
+        }
+    }
+
+    fn drag(camera: &mut Camera, action: Action, delta_x: f32, delta_y: f32) {
+        camera.process_action(action, ElementState::Pressed);
+        camera.process_mouse_motion(delta_x, delta_y);
+        camera.process_action(action, ElementState::Released);
+    }
+
+    #[test]
+    fn basis_is_finite_for_degenerate_views() {
+        let center = Vector3::new(2.0, 2.0, 2.0);
+
+        assert_finite_basis(&Camera::new(center, center));
+        assert_finite_basis(&Camera::new(center + Vector3::new(0.0, 0.0, 10.0), center));
+        assert_finite_basis(&Camera::new(center - Vector3::new(0.0, 0.0, 10.0), center));
+    }
+
+    #[test]
+    fn orbit_stops_short_of_the_poles() {
+        let mut camera = Camera::new(Vector3::new(0.0, -10.0, 0.0), Vector3::zeros());
+
+        for delta_y in [-10000.0, 10000.0] {
+            drag(&mut camera, Action::RotateCamera, 0.0, delta_y);
+
+            let (_, forward, _) = camera.axes();
+            assert!(forward.z.abs() < 1.0);
+            assert!((camera.distance() - 10.0).abs() < 1e-3);
+            assert_finite_basis(&camera);
+        }
+
+        // Starting straight above the pivot turns away from the pole
+        let mut camera = Camera::new(Vector3::new(0.0, 0.0, 10.0), Vector3::zeros());
+        drag(&mut camera, Action::RotateCamera, 50.0, 0.0);
+        assert_finite_basis(&camera);
+        drag(&mut camera, Action::RotateCamera, 0.0, 50.0);
+        assert!(camera.axes().1.z > -1.0);
+        assert_finite_basis(&camera);
+    }
+
+    #[test]
+    fn pan_and_zoom_keep_the_basis_finite() {
+        let mut camera = Camera::new(Vector3::zeros(), Vector3::zeros());
+
+        camera.process_scroll(1.0);
+        assert!((camera.distance() - MIN_DISTANCE).abs() < 1e-6);
+        assert_finite_basis(&camera);
+
+        let look_at = camera.look_at;
+        drag(&mut camera, Action::PanCamera, 100.0, -100.0);
+        assert!(camera.look_at != look_at);
+        assert!((camera.distance() - MIN_DISTANCE).abs() < 1e-6);
+        assert_finite_basis(&camera);
+
+        // Zooming in doesn't pass the pivot
+        camera.process_scroll(1000.0);
+        assert!((camera.distance() - MIN_DISTANCE).abs() < 1e-6);
+        assert_finite_basis(&camera);
+    }
+
+    #[test]
+    fn fly_turns_and_moves_with_a_finite_basis() {
+        let mut camera = Camera::new(Vector3::new(0.0, 0.0, 5.0), Vector3::zeros());
+        camera.toggle_mode();
+
+        drag(&mut camera, Action::RotateCamera, 0.0, -10000.0);
+        assert!((camera.look_from - Vector3::new(0.0, 0.0, 5.0)).norm() < 1e-6);
+        assert_finite_basis(&camera);
+
+        camera.process_action(Action::MoveForward, ElementState::Pressed);
+        camera.update(0.05);
+        camera.process_scroll(2.0);
+        assert!(camera.look_from != Vector3::new(0.0, 0.0, 5.0));
+        assert_finite_basis(&camera);
+    }
+    #[test]
+    fn orthographic_rays_are_parallel_and_zoom_scales_the_view() {
+        let mut camera = Camera::new(Vector3::new(0.0, -10.0, 0.0), Vector3::zeros());
+        assert!(camera.projection().direction == Vector4::zeros());
+
+        camera.toggle_projection();
+        assert!(camera.projection_mode() == Projection::Orthographic);
+        let view_height = 2.0 * (22.5f32).to_radians().tan() * 10.0;
+        assert!((camera.view_height - view_height).abs() < 1e-4);
+
+        // Every ray shares the view direction and starts on the plane through the camera
+        let projection = camera.projection();
+        let direction = projection.direction.xyz();
+        assert!((direction - Vector3::new(0.0, 1.0, 0.0)).norm() < 1e-6);
+        assert!(projection.horizontal.xyz().dot(&direction).abs() < 1e-6);
+        assert!(projection.vertical.xyz().dot(&direction).abs() < 1e-6);
+        assert!((projection.upper_left_corner - projection.origin).xyz().dot(&direction).abs() < 1e-6);
+        assert!((projection.vertical.norm() - view_height).abs() < 1e-4);
+
+        // Zoom scales the view instead of moving the camera, down to a voxel
+        camera.process_scroll(1.0);
+        assert!((camera.view_height - view_height * ZOOM_STEP).abs() < 1e-4);
+        assert!(camera.look_from == Vector3::new(0.0, -10.0, 0.0));
+        camera.process_scroll(1000.0);
+        assert!(camera.view_height == MIN_VIEW_HEIGHT);
+
+        camera.toggle_projection();
+        assert!(camera.projection().direction == Vector4::zeros());
+    }
+    #[test]
+    fn preset_views_look_at_the_center_from_far_enough() {
+        let mut camera = Camera::new(Vector3::new(0.0, -10.0, 0.0), Vector3::zeros());
+        let (lower, upper) = (Vector3::new(0.0, 0.0, 0.0), Vector3::new(4.0, 2.0, 4.0));
+        let radius = 3.0;
+        let views = [
+            (PresetView::Front, Vector3::new(0.0, 1.0, 0.0)),
+            (PresetView::Back, Vector3::new(0.0, -1.0, 0.0)),
+            (PresetView::Left, Vector3::new(1.0, 0.0, 0.0)),
+            (PresetView::Right, Vector3::new(-1.0, 0.0, 0.0)),
+            (PresetView::Isometric, Vector3::new(1.0, 1.0, -1.0).normalize()),
+        ];
+
+        for (view, direction) in views {
+            camera.frame(view, lower, upper);
+            let (_, forward, _) = camera.axes();
+
+            assert!(camera.look_at == Vector3::new(2.0, 1.0, 2.0));
+            assert!((forward - direction).norm() < 1e-4, "{:?}", forward);
+            assert!((camera.distance() - radius / (22.5f32).to_radians().sin()).abs() < 1e-4);
+            assert!((camera.view_height - 2.0 * radius).abs() < 1e-4);
+        }
+
+        // Top looks down with the back of the scene up
+        camera.frame(PresetView::Top, lower, upper);
+        let (_, forward, up) = camera.axes();
+        assert!(forward.z < -0.99 && up.y > 0.99);
+        assert_finite_basis(&camera);
+
+        camera.frame(PresetView::Bottom, lower, upper);
+        assert!(camera.axes().1.z > 0.99);
+        assert_finite_basis(&camera);
+    }
+
+    #[test]
+    fn go_to_returns_to_a_bookmark() {
+        let mut camera = Camera::new(Vector3::new(0.0, -10.0, 0.0), Vector3::zeros());
+        let bookmark = camera.bookmark("start");
+        assert!(bookmark.name == "start");
+
+        drag(&mut camera, Action::RotateCamera, 100.0, 50.0);
+        drag(&mut camera, Action::PanCamera, 30.0, 0.0);
+        assert!(camera.look_from != bookmark.look_from && camera.look_at != bookmark.look_at);
+
+        camera.go_to(&bookmark);
+        assert!(camera.look_from == bookmark.look_from && camera.look_at == bookmark.look_at);
+
+        // Copy at the bookmark doesn't keep moving with held keys
+        camera.process_action(Action::MoveForward, ElementState::Pressed);
+        let mut copy = camera.at(&bookmark);
+        copy.update(0.05);
+        assert!(copy.look_from == bookmark.look_from && copy.look_at == bookmark.look_at);
+    }
+    #[test]
+    fn update_accelerates_with_the_frame_time_up_to_the_speed_of_the_modifiers() {
+        let start = Vector3::new(0.0, -10.0, 0.0);
+        let moved = |modifiers: ModifiersState, frames: u32, delta_time: f32| {
+            let mut camera = Camera::new(start, Vector3::zeros());
+            camera.process_modifiers(modifiers);
+            camera.process_action(Action::MoveForward, ElementState::Pressed);
+
+            for _ in 0..frames {
+                camera.update(delta_time);
+            }
+            camera.look_from.y - start.y
+        };
+
+        // Distance depends on the time, not on how it's split into frames
+        let coarse = moved(ModifiersState::empty(), 20, 0.05);
+        let fine = moved(ModifiersState::empty(), 200, 0.005);
+        assert!(coarse > 0.0 && (coarse - fine).abs() < 0.05 * fine, "{} {}", coarse, fine);
+
+        // Velocity approaches the speed of the held keys without passing it
+        for (modifiers, speed) in [
+            (ModifiersState::empty(), MOVE_SPEED),
+            (ModifiersState::SHIFT, MOVE_SPEED * FAST_SPEED),
+            (ModifiersState::CTRL, MOVE_SPEED * SLOW_SPEED),
+        ] {
+            let distance = moved(modifiers, 100, 0.05);
+            assert!(distance < speed * 5.0 && distance > speed * 4.0, "{} {}", distance, speed);
+        }
+
+        // Long frames are cut short, so the camera doesn't jump
+        let jump = moved(ModifiersState::empty(), 1, 10.0);
+        assert!(jump == moved(ModifiersState::empty(), 1, MAX_DELTA_TIME));
+        assert!(jump < MOVE_SPEED * MAX_DELTA_TIME);
+
+        // Releasing the key slows the camera to a stop
+        let mut camera = Camera::new(start, Vector3::zeros());
+        camera.process_action(Action::MoveForward, ElementState::Pressed);
+        camera.update(0.05);
+        camera.process_action(Action::MoveForward, ElementState::Released);
+        for _ in 0..100 {
+            camera.update(0.05);
+        }
+        let stopped = camera.look_from;
+        camera.update(0.05);
+        assert!(camera.look_from == stopped && camera.controller.velocity == Vector3::zeros());
+    }
 }
\ No newline at end of file
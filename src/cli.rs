@@ -0,0 +1,568 @@
+use std::path::PathBuf;
+use nalgebra::Vector3;
+use super::{
+    world::MAX_CHUNK_DEPTH,
+    terrain::{
+        Terrain,
+        TerrainShape,
+        MAX_OCTAVES
+    },
+    vulkan::Validation,
+};
+
+pub const USAGE: &str = "\
+Usage:
+    voxel_editor [scene] [options]        Open the editor with a scene file or the demo scene
+    voxel_editor render <scene> <png>     Render a scene file to a PNG image without a window
+    voxel_editor convert <input> <output> Convert a scene file or a MagicaVoxel .vox file into
+                                          a scene file or a Wavefront .obj mesh
+    voxel_editor info <scene>             Print bounds, depth, voxel and node counts of a scene
+    voxel_editor frames <scene> <dir>     Render a turntable or a path between bookmarks as
+                                          numbered PNG images into a directory
+    voxel_editor bindings                 Print the default key and mouse bindings in the format
+                                          of a bindings file
+    voxel_editor shape <expression> <output> [options]
+                                          Voxelize a shape centered in a scene file or an .obj
+                                          mesh, like 'subtract(box(6, 6, 6), sphere(8))'
+    voxel_editor terrain <output> [options]
+                                          Generate a height field into a scene file or an .obj mesh
+    voxel_editor replay <recording> <png> [scene] [options]
+                                          Replay a recording with the CPU renderer and write its
+                                          last frame to a PNG image, takes the editor options
+    voxel_editor --help                   Print this help
+
+Editor options:
+    --new <depth>           Start with an empty scene made of chunks of given octree depth
+                            from 1 to 4, a scene holds up to 64 chunks shared with the
+                            models it places
+    --size <WxH>            Window size (default: 1280x720)
+    --look-from <x,y,z>     Camera position (default: in front of the scene)
+    --look-at <x,y,z>       Point the camera looks at (default: center of the scene)
+    --fov <degrees>         Vertical field of view below 180 (default: 45)
+    --validation <level>    Vulkan validation messages to print: off, error, warning, info
+                            or verbose (default: info)
+    --bindings <file>       Key and mouse bindings replacing the defaults (default:
+                            voxel_editor/bindings.txt in the config directory if it exists)
+    --record <file>         Write the input and the frames to a file to replay them later
+    --replay <file>         Replay a recording before taking input, the scene, options and
+                            bindings have to be the ones it was recorded with
+
+Render options:
+    --look-from <x,y,z>     Camera position (default: in front of the octree)
+    --look-at <x,y,z>       Point the camera looks at (default: center of the octree)
+    --fov <degrees>         Vertical field of view below 180 (default: 45)
+    --resolution <WxH>      Image size in pixels (default: 1280x720)
+    --orthographic          Parallel projection framing look at like the field of view does
+    --dag                   Render the scene compressed into a sparse voxel DAG
+
+Frames options, on top of the render options:
+    --frames <count>        Amount of frames (default: 60)
+    --path <name,name,...>  Bookmarks the camera goes through instead of turning around
+                            the point it looks at
+
+Shape options:
+    --depth <depth>         Octree depth of the scene from 1 to 4 (default: 4)
+
+Terrain options:
+    --depth <depth>         Octree depth of the scene from 1 to 4 (default: 4)
+    --seed <number>         Seed of the noise (default: 0)
+    --scale <voxels>        Size of the largest terrain features (default: 16)
+    --octaves <count>       Amount of noise layers adding finer detail from 1 to 16 (default: 4)
+    --heights <min,max>     Range of the column heights in voxels (default: 2,12)
+    --ridged                Sharp ridges instead of rolling hills
+    --caves <threshold>     Carve out voxels whose 3D noise is above a threshold in [-1, 1]
+
+Shapes, with shapes before the numbers in the arguments and rotations in degrees:
+    sphere(radius)  box(x, y, z)  torus(major, minor)  capsule(ax, ay, az, bx, by, bz, radius)
+    union(a, b)  intersect(a, b)  subtract(a, b)  smooth_union(a, b, smoothness)
+    smooth_subtract(a, b, smoothness)  translate(a, x, y, z)  rotate(a, x, y, z)  scale(a, factor)";
+
+pub enum Command {
+    Help,
+    Editor(EditorOptions),
+    Render(RenderOptions),
+    Convert {
+        input: PathBuf,
+        output: PathBuf,
+    },
+    Info(PathBuf),
+    Frames(FramesOptions),
+    Bindings,
+    Replay(ReplayOptions),
+    Shape(ShapeOptions),
+    Terrain(TerrainOptions),
+}
+
+pub struct EditorOptions {
+    pub scene: Option<PathBuf>,
+    pub new_depth: Option<u32>,
+    pub width: u32,
+    pub height: u32,
+    pub look_from: Option<Vector3<f32>>,
+    pub look_at: Option<Vector3<f32>>,
+    pub field_of_view: f32,
+    pub validation: Validation,
+    pub bindings: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+}
+
+pub struct ReplayOptions {
+    pub editor: EditorOptions,
+    pub recording: PathBuf,
+    pub output: PathBuf,
+}
+
+pub struct ShapeOptions {
+    pub expression: String,
+    pub output: PathBuf,
+    pub depth: u32,
+}
+
+pub struct TerrainOptions {
+    pub terrain: Terrain,
+    pub output: PathBuf,
+    pub depth: u32,
+}
+
+pub struct RenderOptions {
+    pub scene: PathBuf,
+    pub output: PathBuf,
+    pub look_from: Option<Vector3<f32>>,
+    pub look_at: Option<Vector3<f32>>,
+    pub field_of_view: f32,
+    pub width: u32,
+    pub height: u32,
+    pub orthographic: bool,
+    pub dag: bool,
+}
+
+pub struct FramesOptions {
+    pub render: RenderOptions, // Output is the directory
+    pub frame_count: u32,
+    pub keyframes: Vec<String>, // Turntable when empty
+}
+
+pub fn parse(args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let args = args.collect::<Vec<_>>();
+
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        return Ok(Command::Help);
+    }
+
+    let mut args = args.into_iter().peekable();
+
+    match args.peek().map(String::as_str) {
+        Some("render") => parse_render(args.skip(1), "a scene file and an output image").map(Command::Render),
+        Some("frames") => parse_frames(args.skip(1)).map(Command::Frames),
+        Some("convert") => parse_paths(args.skip(1), "an input and an output file").map(|[input, output]| Command::Convert { input, output }),
+        Some("info") => parse_paths(args.skip(1), "a scene file").map(|[scene]| Command::Info(scene)),
+        Some("bindings") => parse_paths(args.skip(1), "no arguments").map(|[]| Command::Bindings),
+        Some("replay") => parse_replay(args.skip(1)).map(Command::Replay),
+        Some("shape") => parse_shape(args.skip(1)).map(Command::Shape),
+        Some("terrain") => parse_terrain(args.skip(1)).map(Command::Terrain),
+        // Editor takes no command, so anything else is its scene file or an option
+        _ => parse_editor(args).map(Command::Editor),
+    }
+}
+
+fn parse_editor(mut args: impl Iterator<Item = String>) -> Result<EditorOptions, String> {
+    let mut options = EditorOptions {
+        scene: None,
+        new_depth: None,
+        width: 1280,
+        height: 720,
+        look_from: None,
+        look_at: None,
+        field_of_view: 45.0,
+        validation: Validation::Info,
+        bindings: None,
+        record: None,
+        replay: None,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--new" => options.new_depth = Some(parse_depth(&value(&arg, args.next())?)?),
+            "--size" => (options.width, options.height) = parse_resolution(&value(&arg, args.next())?)?,
+            "--look-from" => options.look_from = Some(parse_vector(&value(&arg, args.next())?)?),
+            "--look-at" => options.look_at = Some(parse_vector(&value(&arg, args.next())?)?),
+            "--fov" => options.field_of_view = parse_field_of_view(&value(&arg, args.next())?)?,
+            "--validation" => options.validation = parse_validation(&value(&arg, args.next())?)?,
+            "--bindings" => options.bindings = Some(PathBuf::from(value(&arg, args.next())?)),
+            "--record" => options.record = Some(PathBuf::from(value(&arg, args.next())?)),
+            "--replay" => options.replay = Some(PathBuf::from(value(&arg, args.next())?)),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option '{}'", arg)),
+            _ if options.scene.is_some() => return Err(format!("Unexpected argument '{}'", arg)),
+            _ => options.scene = Some(PathBuf::from(arg)),
+        }
+    }
+
+    if options.scene.is_some() && options.new_depth.is_some() {
+        return Err(String::from("Either open a scene file or create a new one with '--new'"));
+    }
+
+    check_view(options.look_from, options.look_at)?;
+
+    // Replayed input would be recorded with the times it was recorded with
+    if options.record.is_some() && options.replay.is_some() {
+        return Err(String::from("Either record or replay"));
+    }
+
+    Ok(options)
+}
+
+fn parse_render(mut args: impl Iterator<Item = String>, expected: &str) -> Result<RenderOptions, String> {
+    let mut paths = Vec::new();
+    let mut options = RenderOptions {
+        scene: PathBuf::new(),
+        output: PathBuf::new(),
+        look_from: None,
+        look_at: None,
+        field_of_view: 45.0,
+        width: 1280,
+        height: 720,
+        orthographic: false,
+        dag: false,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--look-from" => options.look_from = Some(parse_vector(&value(&arg, args.next())?)?),
+            "--look-at" => options.look_at = Some(parse_vector(&value(&arg, args.next())?)?),
+            "--fov" => options.field_of_view = parse_field_of_view(&value(&arg, args.next())?)?,
+            "--resolution" => (options.width, options.height) = parse_resolution(&value(&arg, args.next())?)?,
+            "--orthographic" => options.orthographic = true,
+            "--dag" => options.dag = true,
+            _ if arg.starts_with("--") => return Err(format!("Unknown option '{}'", arg)),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    check_view(options.look_from, options.look_at)?;
+
+    match <[PathBuf; 2]>::try_from(paths) {
+        Ok([scene, output]) => {
+            options.scene = scene;
+            options.output = output;
+            Ok(options)
+        },
+        Err(_) => Err(format!("Expected {}", expected)),
+    }
+}
+
+// Everything but the frames options goes to the render options
+fn parse_frames(mut args: impl Iterator<Item = String>) -> Result<FramesOptions, String> {
+    let mut frame_count = 60;
+    let mut keyframes = Vec::new();
+    let mut render_args = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => frame_count = parse_number(&value(&arg, args.next())?)?,
+            "--path" => keyframes = value(&arg, args.next())?.split(',').map(String::from).collect(),
+            _ => render_args.push(arg),
+        }
+    }
+
+    if frame_count == 0 {
+        return Err(String::from("Frame count can't be zero"));
+    }
+
+    Ok(FramesOptions {
+        render: parse_render(render_args.into_iter(), "a scene file and an output directory")?,
+        frame_count,
+        keyframes,
+    })
+}
+
+fn parse_shape(mut args: impl Iterator<Item = String>) -> Result<ShapeOptions, String> {
+    let mut positional = Vec::new();
+    let mut depth = 4;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--depth" => depth = parse_depth(&value(&arg, args.next())?)?,
+            _ if arg.starts_with("--") => return Err(format!("Unknown option '{}'", arg)),
+            _ => positional.push(arg),
+        }
+    }
+
+    match <[String; 2]>::try_from(positional) {
+        Ok([expression, output]) => Ok(ShapeOptions {
+            expression,
+            output: PathBuf::from(output),
+            depth,
+        }),
+        Err(_) => Err(String::from("Expected a shape and an output file")),
+    }
+}
+
+fn parse_terrain(mut args: impl Iterator<Item = String>) -> Result<TerrainOptions, String> {
+    let mut terrain = Terrain::default();
+    let mut output = None;
+    let mut depth = 4;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--depth" => depth = parse_depth(&value(&arg, args.next())?)?,
+            "--seed" => terrain.seed = parse_number(&value(&arg, args.next())?)?,
+            "--scale" => terrain.scale = parse_positive(&value(&arg, args.next())?)?,
+            "--octaves" => terrain.octaves = parse_octaves(&value(&arg, args.next())?)?,
+            "--heights" => (terrain.min_height, terrain.max_height) = parse_heights(&value(&arg, args.next())?)?,
+            "--ridged" => terrain.shape = TerrainShape::Ridged,
+            "--caves" => terrain.cave_threshold = Some(parse_number(&value(&arg, args.next())?)?),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option '{}'", arg)),
+            _ if output.is_some() => return Err(format!("Unexpected argument '{}'", arg)),
+            _ => output = Some(PathBuf::from(arg)),
+        }
+    }
+
+    Ok(TerrainOptions {
+        terrain,
+        output: output.ok_or_else(|| String::from("Expected an output file"))?,
+        depth,
+    })
+}
+
+fn parse_replay(mut args: impl Iterator<Item = String>) -> Result<ReplayOptions, String> {
+    let [recording, output] = parse_paths(args.by_ref().take(2), "a recording and an output image")?;
+    let editor = parse_editor(args)?;
+
+    if editor.record.is_some() || editor.replay.is_some() {
+        return Err(String::from("Replay takes the recording without '--record' or '--replay'"));
+    }
+
+    Ok(ReplayOptions {
+        editor,
+        recording,
+        output,
+    })
+}
+
+// Commands taking only paths and no options
+fn parse_paths<const N: usize>(args: impl Iterator<Item = String>, expected: &str) -> Result<[PathBuf; N], String> {
+    let paths = args
+        .map(|arg| match arg.starts_with("--") {
+            true => Err(format!("Unknown option '{}'", arg)),
+            false => Ok(PathBuf::from(arg)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    <[PathBuf; N]>::try_from(paths).map_err(|_| format!("Expected {}", expected))
+}
+
+fn value(option: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("Missing value for '{}'", option))
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value.trim().parse().map_err(|_| format!("Invalid number '{}'", value))
+}
+
+fn parse_vector(value: &str) -> Result<Vector3<f32>, String> {
+    let components = value
+        .split(',')
+        .map(parse_number)
+        .collect::<Result<Vec<f32>, _>>()?;
+
+    match components[..] {
+        [x, y, z] => Ok(Vector3::new(x, y, z)),
+        _ => Err(format!("Expected three comma separated numbers, got '{}'", value)),
+    }
+}
+
+fn parse_positive(value: &str) -> Result<f32, String> {
+    match parse_number(value)? {
+        number if number > 0.0 => Ok(number),
+        _ => Err(format!("Expected a positive number, got '{}'", value)),
+    }
+}
+
+fn parse_field_of_view(value: &str) -> Result<f32, String> {
+    match parse_number(value)? {
+        degrees if 0.0 < degrees && degrees < 180.0 => Ok(degrees),
+        _ => Err(format!("Field of view must be between 0 and 180 degrees, got '{}'", value)),
+    }
+}
+
+// Camera at the point it looks at has no view direction
+fn check_view(look_from: Option<Vector3<f32>>, look_at: Option<Vector3<f32>>) -> Result<(), String> {
+    match (look_from, look_at) {
+        (Some(look_from), Some(look_at)) if look_from == look_at => Err(String::from("Look from and look at can't be the same point")),
+        _ => Ok(()),
+    }
+}
+
+fn parse_heights(value: &str) -> Result<(f32, f32), String> {
+    let heights = value
+        .split(',')
+        .map(parse_number)
+        .collect::<Result<Vec<f32>, _>>()?;
+
+    match heights[..] {
+        [min, max] if 0.0 <= min && min <= max => Ok((min, max)),
+        _ => Err(format!("Expected two comma separated heights from lowest to highest, got '{}'", value)),
+    }
+}
+
+fn parse_depth(value: &str) -> Result<u32, String> {
+    match parse_number(value)? {
+        depth @ 1..=MAX_CHUNK_DEPTH => Ok(depth),
+        _ => Err(format!("Depth must be in range [1, {}], got '{}'", MAX_CHUNK_DEPTH, value)),
+    }
+}
+
+fn parse_octaves(value: &str) -> Result<u32, String> {
+    match parse_number(value)? {
+        octaves @ 1..=MAX_OCTAVES => Ok(octaves),
+        _ => Err(format!("Octaves must be in range [1, {}], got '{}'", MAX_OCTAVES, value)),
+    }
+}
+
+fn parse_validation(value: &str) -> Result<Validation, String> {
+    match value {
+        "off" => Ok(Validation::Off),
+        "error" => Ok(Validation::Error),
+        "warning" => Ok(Validation::Warning),
+        "info" => Ok(Validation::Info),
+        "verbose" => Ok(Validation::Verbose),
+        _ => Err(format!("Unknown validation level '{}'", value)),
+    }
+}
+
+fn parse_resolution(value: &str) -> Result<(u32, u32), String> {
+    match value.split_once('x') {
+        Some((width, height)) => {
+            let (width, height) = (parse_number(width)?, parse_number(height)?);
+
+            if width == 0 || height == 0 {
+                return Err(format!("Resolution can't be empty, got '{}'", value));
+            }
+
+            Ok((width, height))
+        },
+        None => Err(format!("Expected resolution as WxH, got '{}'", value)),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_line(line: &str) -> Result<Command, String> {
+        parse(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parse_editor_options() {
+        let Ok(Command::Editor(options)) = parse_line("scene.vxed --size 800x600 --look-from 1,2,3 --fov 60 --validation off") else {
+            panic!("Expected editor options");
+        };
+
+        assert_eq!(options.scene, Some(PathBuf::from("scene.vxed")));
+        assert_eq!((options.width, options.height), (800, 600));
+        assert_eq!(options.look_from, Some(Vector3::new(1.0, 2.0, 3.0)));
+        assert_eq!(options.look_at, None);
+        assert_eq!(options.field_of_view, 60.0);
+        assert!(matches!(options.validation, Validation::Off));
+
+        let Ok(Command::Editor(options)) = parse_line("--new 3") else {
+            panic!("Expected editor options");
+        };
+
+        assert_eq!((options.scene, options.new_depth), (None, Some(3)));
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert!(matches!(parse_line("frames a.vxed --help"), Ok(Command::Help)));
+        assert!(matches!(parse_line("bindings"), Ok(Command::Bindings)));
+        assert!(matches!(parse_line("info a.vxed"), Ok(Command::Info(path)) if path.as_os_str() == "a.vxed"));
+        assert!(matches!(
+            parse_line("convert a.vox b.obj"),
+            Ok(Command::Convert { input, output }) if input.as_os_str() == "a.vox" && output.as_os_str() == "b.obj"
+        ));
+
+        let Ok(Command::Render(options)) = parse_line("render a.vxed a.png --resolution 64x32 --orthographic --dag") else {
+            panic!("Expected render options");
+        };
+
+        assert_eq!((options.scene, options.output), (PathBuf::from("a.vxed"), PathBuf::from("a.png")));
+        assert_eq!((options.width, options.height), (64, 32));
+        assert!(options.orthographic && options.dag);
+
+        let Ok(Command::Frames(options)) = parse_line("frames a.vxed frames --frames 10 --path a,b --fov 30") else {
+            panic!("Expected frames options");
+        };
+
+        assert_eq!((options.frame_count, options.keyframes), (10, vec![String::from("a"), String::from("b")]));
+        assert_eq!((options.render.output, options.render.field_of_view), (PathBuf::from("frames"), 30.0));
+
+        let Ok(Command::Replay(options)) = parse_line("replay input.txt last.png scene.vxed --size 320x240") else {
+            panic!("Expected replay options");
+        };
+
+        assert_eq!((options.recording, options.output), (PathBuf::from("input.txt"), PathBuf::from("last.png")));
+        assert_eq!((options.editor.scene, options.editor.width), (Some(PathBuf::from("scene.vxed")), 320));
+
+        let Ok(Command::Shape(options)) = parse(["shape", "union(sphere(3), box(1, 2, 3))", "shape.obj", "--depth", "3"].map(String::from).into_iter()) else {
+            panic!("Expected shape options");
+        };
+
+        assert_eq!((options.expression.as_str(), options.depth), ("union(sphere(3), box(1, 2, 3))", 3));
+
+        let Ok(Command::Terrain(options)) = parse_line("terrain hills.vxed --seed 9 --heights 1,5 --ridged --caves 0.4 --octaves 16") else {
+            panic!("Expected terrain options");
+        };
+
+        assert_eq!((options.terrain.seed, options.terrain.min_height, options.terrain.max_height), (9, 1.0, 5.0));
+        assert!(matches!(options.terrain.shape, TerrainShape::Ridged));
+        assert_eq!((options.terrain.cave_threshold, options.terrain.octaves, options.depth), (Some(0.4), 16, 4));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_arguments() {
+        let lines = [
+            "--new 0",
+            "--new 5",
+            "--new 22",
+            "scene.vxed --new 3",
+            "--record a.txt --replay b.txt",
+            "--size 0x10",
+            "--size 10",
+            "--look-at 1,2",
+            "--validation loud",
+            "--fov",
+            "--fov 0",
+            "--fov -30",
+            "--fov 180",
+            "--fov NaN",
+            "--look-from 1,2,3 --look-at 1,2,3",
+            "render a.vxed a.png --fov 200",
+            "render a.vxed a.png --look-at 0,0,0 --look-from 0,0,0",
+            "frames a.vxed dir --fov 0",
+            "--unknown",
+            "a.vxed b.vxed",
+            "render a.vxed",
+            "render a.vxed a.png --bindings b.txt",
+            "frames a.vxed dir --frames 0",
+            "convert a.vxed",
+            "info a.vxed --dag",
+            "bindings extra",
+            "replay input.txt last.png --record again.txt",
+            "shape sphere(3)",
+            "terrain",
+            "terrain a.vxed --heights 5,1",
+            "terrain a.vxed --scale 0",
+            "terrain a.vxed --octaves 0",
+            "terrain a.vxed --octaves 17",
+            "terrain a.vxed --octaves 100000000",
+        ];
+
+        for line in lines {
+            assert!(parse_line(line).is_err(), "{}", line);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,395 @@
+use std::path::PathBuf;
+use nalgebra::Vector3;
+use super::{
+    format,
+    sdf::Sdf,
+    camera::Camera,
+    frames::{
+        self,
+        CameraPath
+    },
+    history::Edit,
+    selection::Selection,
+    world::{
+        World,
+        MAX_OCTREES
+    },
+};
+
+pub const HELP: &str = "fill x y z x y z | clear [x y z x y z] | sphere x y z radius | color rrggbb | save path | \
+    export path [overwrite] | bookmark name | goto name | bookmarks | turntable frames directory | path frames directory name,name,...";
+
+pub enum Command {
+    Fill(Selection),
+    Clear(Option<Selection>), // Clears the current selection when there is no box
+    Sphere {
+        center: Vector3<f32>,
+        radius: f32,
+    },
+    Color(u32), // Sets the color the tools, fill and sphere use
+    Save(PathBuf),
+    Export {
+        path: PathBuf, // Selection is saved as a scene file
+        overwrite: bool, // Existing files are only replaced when asked to
+    },
+    Bookmark(String), // Saves the camera position under a name
+    GoTo(String),
+    Bookmarks,
+    Frames {
+        frame_count: u32,
+        directory: PathBuf,
+        keyframes: Vec<String>, // Turntable when empty
+    },
+}
+
+/// Line of text typed into the editor, opened and closed with the grave key
+#[derive(Default)]
+pub struct Console {
+    open: bool,
+    input: String,
+    message: String, // Result of the last command
+}
+
+impl Console {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn set_message(&mut self, message: String) {
+        self.message = message;
+    }
+
+    pub fn push(&mut self, character: char) {
+        // Grave opens and closes the console, so it never ends up in the input
+        if !character.is_control() && character != '`' {
+            self.input.push(character);
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+
+    /// Opens the console with the start of a command typed in
+    pub fn prompt(&mut self, input: &str) {
+        self.open = true;
+        self.input = String::from(input);
+    }
+
+    /// Takes the typed line out of the console
+    pub fn submit(&mut self) -> String {
+        std::mem::take(&mut self.input)
+    }
+}
+
+pub fn parse(line: &str) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let name = words.next().ok_or_else(|| String::from("Empty command"))?;
+    let args = words.collect::<Vec<_>>();
+
+    match (name, args.len()) {
+        ("fill", 6) => Ok(Command::Fill(parse_box(&args)?)),
+        ("clear", 0) => Ok(Command::Clear(None)),
+        ("clear", 6) => Ok(Command::Clear(Some(parse_box(&args)?))),
+        ("sphere", 4) => {
+            let numbers = parse_numbers(&args)?;
+
+            Ok(Command::Sphere {
+                center: Vector3::new(numbers[0], numbers[1], numbers[2]),
+                radius: numbers[3],
+            })
+        },
+        ("color", 1) => Ok(Command::Color(parse_color(args[0])?)),
+        ("save", 1) => Ok(Command::Save(PathBuf::from(args[0]))),
+        ("export", 1) => Ok(Command::Export { path: PathBuf::from(args[0]), overwrite: false }),
+        ("export", 2) if args[1] == "overwrite" => Ok(Command::Export { path: PathBuf::from(args[0]), overwrite: true }),
+        // Names may have spaces
+        ("bookmark", 1..) => Ok(Command::Bookmark(args.join(" "))),
+        ("goto", 1..) => Ok(Command::GoTo(args.join(" "))),
+        ("bookmarks", 0) => Ok(Command::Bookmarks),
+        ("turntable", 2) | ("path", 3) => Ok(Command::Frames {
+            frame_count: parse_frame_count(args[0])?,
+            directory: PathBuf::from(args[1]),
+            keyframes: args.get(2).map(|names| names.split(',').map(String::from).collect()).unwrap_or_default(),
+        }),
+        ("fill" | "clear" | "sphere" | "color" | "save" | "export" | "bookmark" | "goto" | "bookmarks" | "turntable" | "path", _) => Err(format!("Wrong number of arguments for '{}', usage: {}", name, HELP)),
+        _ => Err(format!("Unknown command '{}', commands: {}", name, HELP)),
+    }
+}
+
+/// Runs the command against the world and returns a message about what it did. Voxels are
+/// changed in the active layer and recorded in the edit, which the caller pushes to the history.
+/// New voxels get the active color. Frames are rendered with the CPU renderer in given size.
+pub fn execute(
+    command: Command, 
+    world: &mut World, 
+    camera: &mut Camera, 
+    selection: Option<&Selection>, 
+    edit: &mut Edit,
+    color: &mut u32,
+    (width, height): (u32, u32)
+) -> Result<String, String> {
+    let edits = matches!(command, Command::Fill(_) | Command::Clear(_) | Command::Sphere { .. });
+
+    if edits && world.editable_layer().is_none() {
+        return Err(String::from("Active layer is locked"));
+    }
+
+    match command {
+        Command::Fill(selection) => {
+            fits(world, &selection)?;
+            selection.fill(world, edit, *color);
+            Ok(filled("Filled the box", edit))
+        },
+        Command::Clear(selection_box) => {
+            let selection = selection_box.as_ref().or(selection).ok_or_else(|| String::from("Nothing is selected"))?;
+
+            fits(world, selection)?;
+            selection.clear(world, edit);
+            Ok(String::from("Cleared the box"))
+        },
+        Command::Sphere { center, radius } => {
+            let sphere = Sdf::sphere(radius).translate(center);
+            let bounds = Selection::new(center.add_scalar(-radius), center.add_scalar(radius));
+
+            fits(world, &bounds)?;
+
+            // Voxel belongs to the sphere when its center does
+            for pos in bounds.voxels().filter(|pos| sphere.distance(pos.add_scalar(0.5)) <= 0.0) {
+                edit.set(world, pos, *color);
+            }
+
+            Ok(filled("Filled the sphere", edit))
+        },
+        Command::Color(new_color) => {
+            *color = new_color;
+            Ok(format!("Color is #{:06x}", new_color))
+        },
+        Command::Save(path) => format::save(&path, world)
+            .map(|_| format!("Saved {}", path.display()))
+            .map_err(|error| format!("Couldn't save {}: {}", path.display(), error)),
+        Command::Export { path, overwrite } => {
+            let selection = selection.ok_or_else(|| String::from("Nothing is selected"))?;
+
+            if path.exists() && !overwrite {
+                return Err(format!("{} already exists, add 'overwrite' to replace it", path.display()));
+            }
+
+            selection.export(world, &path)
+                .map(|_| format!("Exported the selection to {}", path.display()))
+                .map_err(|error| format!("Couldn't export the selection to {}: {}", path.display(), error))
+        },
+        Command::Bookmark(name) => {
+            world.set_bookmark(camera.bookmark(&name));
+            Ok(format!("Bookmarked '{}'", name))
+        },
+        Command::GoTo(name) => {
+            let bookmark = world.find_bookmark(&name).ok_or_else(|| format!("No bookmark named '{}'", name))?;
+
+            camera.go_to(bookmark);
+            Ok(format!("Went to '{}'", name))
+        },
+        Command::Bookmarks => {
+            let names = world.bookmarks().iter().map(|bookmark| bookmark.name.as_str()).collect::<Vec<_>>();
+
+            match names.is_empty() {
+                true => Ok(String::from("No bookmarks")),
+                false => Ok(names.join(", ")),
+            }
+        },
+        Command::Frames { frame_count, directory, keyframes } => {
+            let path = match keyframes.is_empty() {
+                true => CameraPath::Turntable,
+                false => CameraPath::keyframes(world, &keyframes)?,
+            };
+
+            frames::export(world, camera, &path, frame_count, width, height, &directory)?;
+            Ok(format!("Wrote {} frames to {}", frame_count, directory.display()))
+        },
+    }
+}
+
+fn filled(message: &str, edit: &Edit) -> String {
+    match edit.missing() {
+        0 => String::from(message),
+        missing => format!("{}, {} voxels didn't fit into the {} chunks the scene shares with its models", message, missing, MAX_OCTREES),
+    }
+}
+
+// Boxes larger than the world can hold would take ages to go through voxel by voxel
+fn fits(world: &World, selection: &Selection) -> Result<(), String> {
+    match selection.volume() <= world.capacity() {
+        true => Ok(()),
+        false => Err(format!("Box is larger than the {} voxels {} chunks can hold", world.capacity(), MAX_OCTREES)),
+    }
+}
+
+fn parse_box(args: &[&str]) -> Result<Selection, String> {
+    let numbers = parse_numbers(args)?;
+
+    Ok(Selection::new(
+        Vector3::new(numbers[0], numbers[1], numbers[2]),
+        Vector3::new(numbers[3], numbers[4], numbers[5]),
+    ))
+}
+
+// Hex RGB like CSS without the shorthand, # is optional
+fn parse_color(arg: &str) -> Result<u32, String> {
+    let digits = arg.strip_prefix('#').unwrap_or(arg);
+
+    match digits.len() == 6 && digits.chars().all(|digit| digit.is_ascii_hexdigit()) {
+        true => Ok(u32::from_str_radix(digits, 16).unwrap()),
+        false => Err(format!("Invalid color '{}'", arg)),
+    }
+}
+
+fn parse_frame_count(arg: &str) -> Result<u32, String> {
+    match arg.parse() {
+        Ok(0) | Err(_) => Err(format!("Invalid frame count '{}'", arg)),
+        Ok(frame_count) => Ok(frame_count),
+    }
+}
+
+fn parse_numbers(args: &[&str]) -> Result<Vec<f32>, String> {
+    args.iter()
+        .map(|arg| match arg.parse::<f32>() {
+            Ok(number) if number.is_finite() => Ok(number),
+            _ => Err(format!("Invalid number '{}'", arg)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(world: &mut World, selection: Option<&Selection>, line: &str) -> Result<String, String> {
+        let mut camera = Camera::new(Vector3::new(0.0, -10.0, 0.0), Vector3::zeros());
+
+        execute(parse(line)?, world, &mut camera, selection, &mut Edit::default(), &mut 0xFF0000, (1, 1))
+    }
+
+    fn sorted_voxels(world: &World) -> Vec<Vector3<f32>> {
+        let mut voxels = world.voxels(0).collect::<Vec<_>>();
+        voxels.sort_by(|a, b| a.as_slice().partial_cmp(b.as_slice()).unwrap());
+
+        voxels
+    }
+
+    fn corners(selection: &Selection) -> (Vector3<i32>, Vector3<i32>) {
+        (selection.lower(), selection.upper())
+    }
+
+    #[test]
+    fn parse_edit_commands() {
+        let Ok(Command::Fill(selection)) = parse("fill 3 -1 2.5 0 4 2") else {
+            panic!("fill");
+        };
+        assert_eq!(corners(&selection), (Vector3::new(0, -1, 2), Vector3::new(3, 4, 2)));
+
+        assert!(matches!(parse("clear"), Ok(Command::Clear(None))));
+
+        let Ok(Command::Clear(Some(selection))) = parse("  clear 1 1 1   2 2 2 ") else {
+            panic!("clear");
+        };
+        assert_eq!(corners(&selection), (Vector3::repeat(1), Vector3::repeat(2)));
+
+        let Ok(Command::Sphere { center, radius }) = parse("sphere 1 2 3 4.5") else {
+            panic!("sphere");
+        };
+        assert_eq!((center, radius), (Vector3::new(1.0, 2.0, 3.0), 4.5));
+
+        let Ok(Command::Save(path)) = parse("save scenes/castle.vxed") else {
+            panic!("save");
+        };
+        assert_eq!(path, PathBuf::from("scenes/castle.vxed"));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_commands() {
+        for line in ["fill 1 2 3", "clear 1 2 3 4 5", "sphere 1 2 3", "save", "save a b", "color"] {
+            assert!(parse(line).err().unwrap().starts_with("Wrong number of arguments"), "{}", line);
+        }
+
+        assert!(parse("explode 1 2 3").err().unwrap().starts_with("Unknown command 'explode'"));
+        assert_eq!(parse("").err().unwrap(), "Empty command");
+        assert_eq!(parse("fill 0 0 0 1 x 1").err().unwrap(), "Invalid number 'x'");
+
+        for line in ["fill 0 0 0 nan 0 0", "sphere 0 0 0 inf", "clear 0 -inf 0 1 1 1"] {
+            assert!(parse(line).err().unwrap().starts_with("Invalid number"), "{}", line);
+        }
+    }
+
+    #[test]
+    fn execute_fills_boxes_and_spheres() {
+        let mut world = World::new(3);
+
+        run(&mut world, None, "fill 0 0 0 1 0 2").unwrap();
+
+        let filled = (0..3).flat_map(|z| (0..2).map(move |x| Vector3::new(x as f32, 0.0, z as f32))).collect::<Vec<_>>();
+        let mut expected = filled.clone();
+        expected.sort_by(|a, b| a.as_slice().partial_cmp(b.as_slice()).unwrap());
+
+        assert_eq!(sorted_voxels(&world), expected);
+        assert!(filled.iter().all(|pos| world.color(0, *pos) == 0xFF0000));
+
+        assert_eq!(run(&mut world, None, "clear").err().unwrap(), "Nothing is selected");
+        run(&mut world, None, "clear 0 0 0 9 9 9").unwrap();
+        assert!(sorted_voxels(&world).is_empty());
+
+        // Radius 1 around a voxel center holds the center and its 6 neighbours
+        run(&mut world, None, "sphere 10.5 10.5 10.5 1").unwrap();
+
+        let mut expected = [[0, 0, 0], [-1, 0, 0], [1, 0, 0], [0, -1, 0], [0, 1, 0], [0, 0, -1], [0, 0, 1]]
+            .map(|offset| Vector3::from(offset).add_scalar(10).cast::<f32>())
+            .to_vec();
+        expected.sort_by(|a, b| a.as_slice().partial_cmp(b.as_slice()).unwrap());
+
+        assert_eq!(sorted_voxels(&world), expected);
+    }
+
+    #[test]
+    fn execute_rejects_boxes_larger_than_the_world() {
+        let mut world = World::new(3);
+
+        for line in ["fill 0 0 0 1e10 0 0", "sphere 0 0 0 1e9", "clear -1e20 0 0 1e20 1e20 1e20", "fill 0 0 0 64 64 8"] {
+            assert!(run(&mut world, None, line).err().unwrap().starts_with("Box is larger than"), "{}", line);
+        }
+
+        assert_eq!(world.voxels(0).count(), 0);
+    }
+
+    #[test]
+    fn export_refuses_to_overwrite() {
+        let path = std::env::temp_dir().join(format!("voxel_editor_export_{}.vxed", std::process::id()));
+        let line = format!("export {}", path.display());
+        let mut world = World::new(3);
+        world.insert(0, Vector3::new(1.0, 2.0, 3.0));
+        let selection = Selection::new(Vector3::zeros(), Vector3::repeat(4.0));
+
+        assert!(run(&mut world, None, &line).is_err());
+        assert!(run(&mut world, Some(&selection), &line).is_ok());
+
+        std::fs::write(&path, b"keep").unwrap();
+
+        assert!(run(&mut world, Some(&selection), &line).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"keep");
+
+        assert!(run(&mut world, Some(&selection), &format!("{} overwrite", line)).is_ok());
+        assert_eq!(format::load(&path).unwrap().voxels(0).collect::<Vec<_>>(), [Vector3::new(1.0, 2.0, 3.0)]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
\ No newline at end of file
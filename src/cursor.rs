@@ -1,7 +1,25 @@
-use nalgebra::Vector4;
+use nalgebra::{
+    Vector3,
+    Vector4
+};
 
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub struct Cursor {
-    pos: Vector4<f32>,
-    normal: Vector4<i32>,
+    pub pos: Vector4<f32>,
+    pub normal: Vector4<i32>,
+    pub local_pos: Vector3<f32>, // Voxel of the instance's model, counted from the model's lower corner
+    pub instance: i32, // -1 unless the cursor is on an instance
+}
+
+impl Default for Cursor {
+    // Zero normal means there is no cursor
+    fn default() -> Self {
+        Self {
+            pos: Vector4::zeros(),
+            normal: Vector4::zeros(),
+            local_pos: Vector3::zeros(),
+            instance: -1,
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use super::octree::{
+    Octree,
+    MEMORY_SIZE
+};
+
+// Sparse voxel DAG keeps the octree memory layout, but identical child blocks are stored
+// only once and every parent of such a block points to the same address. The shaders and
+// the CPU raymarcher can traverse it as is, but it must not be edited since a change in
+// a shared block would show up in every subtree that uses it.
+pub struct Dag {
+    octree: Octree,
+    original_size: usize,
+}
+
+impl Dag {
+    pub fn new(octree: &Octree) -> Self {
+        let mut dag = Octree::new(octree.depth, Vec::new());
+        let mut blocks = HashMap::new();
+
+        // Children of the root keep the first 8 descriptors like in the octree
+        let root = octree.descriptors[MEMORY_SIZE - 1];
+        let lowest_level = octree.depth - 1; // Level of descriptors holding only leaf valid masks
+        let root_children = match lowest_level {
+            0 => [0; 8], // Root holds the leaf valid mask itself
+            _ => compress_block(octree, &mut dag, &mut blocks, root, 0, lowest_level),
+        };
+
+        dag.descriptors[..8].copy_from_slice(&root_children);
+        dag.descriptors[MEMORY_SIZE - 1] = root & 0xFF;
+
+        Self {
+            octree: dag,
+            original_size: used_size(octree),
+        }
+    }
+
+    pub fn octree(&self) -> &Octree {
+        &self.octree
+    }
+
+    /// Descriptors used by the source octree for each descriptor used by the DAG
+    pub fn compression_ratio(&self) -> f32 {
+        self.original_size as f32 / used_size(&self.octree) as f32
+    }
+
+    pub fn original_size(&self) -> usize {
+        self.original_size
+    }
+
+    pub fn size(&self) -> usize {
+        used_size(&self.octree)
+    }
+}
+
+// Builds the deduplicated child block of a descriptor at given level (root is level 0)
+fn compress_block(
+    octree: &Octree,
+    dag: &mut Octree,
+    blocks: &mut HashMap<[u32; 8], u32>,
+    descriptor: u32,
+    level: u32,
+    lowest_level: u32,
+) -> [u32; 8] {
+    let mut block = [0; 8];
+
+    for (idx, child) in block.iter_mut().enumerate() {
+        if descriptor & 1 << idx == 0 {
+            continue;
+        }
+
+        let child_descriptor = octree.descriptors[(descriptor >> 8) as usize + idx];
+
+        // Leaf valid masks are compared by value, so they need no children of their own
+        if level + 1 == lowest_level {
+            *child = child_descriptor & 0xFF;
+            continue;
+        }
+
+        let child_block = compress_block(octree, dag, blocks, child_descriptor, level + 1, lowest_level);
+        let address = *blocks.entry(child_block).or_insert_with(|| {
+            dag.free_address += 8;
+            assert!(dag.free_address as usize + 8 < MEMORY_SIZE, "Octree memory is exhausted");
+
+            let address = dag.free_address as usize;
+            dag.descriptors[address..address + 8].copy_from_slice(&child_block);
+
+            dag.free_address
+        });
+
+        *child = child_descriptor & 0xFF | address << 8;
+    }
+
+    block
+}
+
+// Allocated descriptors plus the root
+fn used_size(octree: &Octree) -> usize {
+    octree.free_address as usize + 8 + 1
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Vector3;
+    use crate::raymarch::octree_raymarch;
+
+    // Same few voxels in each of the 8 children of the root of a depth 4 octree
+    fn repeated_octree() -> Octree {
+        let pattern = [Vector3::new(0.0, 0.0, 0.0), Vector3::new(3.0, 1.0, 0.0), Vector3::new(2.0, 5.0, 7.0), Vector3::new(6.0, 6.0, 6.0)];
+        let voxels = (0..8)
+            .map(|idx| Vector3::new(idx & 1, idx >> 1 & 1, idx >> 2 & 1).cast::<f32>() * 8.0)
+            .flat_map(|offset| pattern.map(|pos| pos + offset + Vector3::repeat(16.0)))
+            .collect();
+
+        Octree::new(4, voxels)
+    }
+
+    #[test]
+    fn dag_renders_like_the_octree() {
+        let octree = repeated_octree();
+        let dag = Dag::new(&octree);
+
+        let mut voxels = octree.voxels();
+        let mut dag_voxels = dag.octree().voxels();
+        voxels.sort_by(|a, b| a.as_slice().partial_cmp(b.as_slice()).unwrap());
+        dag_voxels.sort_by(|a, b| a.as_slice().partial_cmp(b.as_slice()).unwrap());
+        assert_eq!(voxels, dag_voxels);
+
+        // Grid of rays through the octree from a few directions
+        let directions = [Vector3::new(1.0, 0.3, 0.2), Vector3::new(-0.4, -1.0, 0.5), Vector3::new(0.1, 0.2, -1.0)];
+        let mut hits = 0;
+
+        for d in directions {
+            for u in 0..32 {
+                for v in 0..32 {
+                    let o = Vector3::repeat(24.0) - d.normalize() * 40.0 + Vector3::new(u as f32 - 16.0, v as f32 - 16.0, 0.5);
+                    let hit = octree_raymarch(&octree, o, d);
+                    let dag_hit = octree_raymarch(dag.octree(), o, d);
+
+                    hits += hit.is_some() as u32;
+                    assert_eq!(hit.map(|hit| (hit.pos, hit.normal, hit.t)), dag_hit.map(|hit| (hit.pos, hit.normal, hit.t)));
+                }
+            }
+        }
+
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn dag_stores_repeated_subtrees_once() {
+        let dag = Dag::new(&repeated_octree());
+
+        assert!(dag.size() < dag.original_size());
+        assert!(dag.compression_ratio() > 1.0);
+
+        // Children of the root, one block shared by all of them and a block for each of the three
+        // octants of the pattern with voxels, plus the root
+        assert_eq!(dag.size(), 8 + 8 + 3 * 8 + 1);
+        assert_eq!(dag.original_size(), 8 + 8 * 8 + 8 * 3 * 8 + 1);
+    }
+}
\ No newline at end of file
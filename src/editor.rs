@@ -0,0 +1,486 @@
+use nalgebra::{
+    Vector3,
+    Vector4
+};
+use winit::{
+    dpi::{
+        PhysicalPosition,
+        PhysicalSize
+    },
+    event::{
+        ElementState,
+        ModifiersState,
+        VirtualKeyCode
+    },
+};
+use super::{
+    raymarch,
+    console::{
+        self,
+        Console
+    },
+    cursor::Cursor,
+    history::{
+        Edit,
+        History
+    },
+    mouse::{
+        Mouse,
+        MouseState,
+        Tool
+    },
+    camera::{
+        Camera,
+        CameraProjection,
+        PresetView
+    },
+    input::{
+        Action,
+        Direction,
+        Input,
+        InputMap
+    },
+    selection::{
+        self,
+        Selection,
+        Clipboard
+    },
+    world::{
+        World,
+        Layer,
+        DEFAULT_COLOR,
+        MAX_OCTREES
+    },
+};
+
+pub const APP_NAME: &str = "Voxel editor";
+
+/// Window events the editor reacts to, which is all that is needed to replay a session
+#[derive(Clone, Copy, PartialEq)]
+pub enum Event {
+    Resized(PhysicalSize<u32>),
+    CursorMoved(PhysicalPosition<f64>),
+    MouseMotion(f32, f32), // Pixels the mouse moved, also outside of the window
+    Input(Input, ElementState),
+    Scroll(f32), // Lines
+    Modifiers(ModifiersState),
+    Character(char),
+}
+
+/// What the window has to catch up with after the editor handled events
+#[derive(Default)]
+pub struct Changes {
+    pub world: bool, // CPU copy of the world has to be uploaded
+    pub title: bool,
+    pub quit: bool,
+}
+
+/// State of the editor that input changes, kept apart from the window and the GPU so that
+/// recorded input can be replayed without them
+pub struct Editor {
+    world: World, // CPU copy of the world buffer which edits are mirrored to
+    history: History,
+    cursor: Cursor, // CPU copy of the cursor buffer
+    selection: Option<Selection>,
+    selecting: bool, // Selection is being dragged out
+    selection_start: Option<Vector3<f32>>,
+    clipboard: Clipboard,
+    console: Console,
+    camera: Camera,
+    input_map: InputMap,
+    mouse: Mouse,
+    tool: Tool,
+    color: u32, // 0xRRGGBB which the tools and the fills use
+    modifiers: ModifiersState,
+    window_size: PhysicalSize<u32>,
+    changes: Changes,
+}
+
+impl Editor {
+    pub fn new(world: World, camera: Camera, input_map: InputMap, window_size: PhysicalSize<u32>) -> Self {
+        Self {
+            world,
+            history: History::default(),
+            cursor: Cursor::default(),
+            selection: None,
+            selecting: false,
+            selection_start: None,
+            clipboard: Clipboard::default(),
+            console: Console::default(),
+            camera,
+            input_map,
+            mouse: Mouse::default(),
+            tool: Tool::Pencil,
+            color: DEFAULT_COLOR,
+            modifiers: ModifiersState::empty(),
+            window_size,
+            changes: Changes::default(),
+        }
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn selection(&self) -> Option<&Selection> {
+        self.selection.as_ref()
+    }
+
+    pub fn window_size(&self) -> PhysicalSize<u32> {
+        self.window_size
+    }
+
+    /// Changes since the last call
+    pub fn take_changes(&mut self) -> Changes {
+        std::mem::take(&mut self.changes)
+    }
+
+    pub fn handle(&mut self, event: Event) {
+        match event {
+            // Minimized window has no size
+            Event::Resized(size) if size.width == 0 || size.height == 0 => (),
+            Event::Resized(size) => {
+                self.window_size = size;
+                self.camera.set_aspect_ratio(size.width as f32 / size.height as f32);
+            },
+            Event::CursorMoved(position) => self.mouse.process_movement(position, self.window_size),
+            Event::MouseMotion(x, y) => self.camera.process_mouse_motion(x, y),
+            Event::Scroll(lines) => self.camera.process_scroll(lines),
+            Event::Modifiers(modifiers) => {
+                self.modifiers = modifiers;
+                self.camera.process_modifiers(modifiers);
+            },
+            Event::Character(character) if self.console.is_open() => self.type_character(character),
+            Event::Character(_) => (),
+            Event::Input(input, state) => match (input, state) {
+                (Input::Key(VirtualKeyCode::Grave), ElementState::Pressed) => self.toggle_console(),
+                (Input::Key(VirtualKeyCode::Escape), ElementState::Pressed) if self.console.is_open() => self.toggle_console(),
+                (Input::Key(VirtualKeyCode::Return), ElementState::Pressed) if self.console.is_open() => self.execute_command(),
+                (Input::Key(VirtualKeyCode::Back), ElementState::Pressed) if self.console.is_open() => self.erase_character(),
+                // Releases still stop held actions, so the camera doesn't keep moving after the console opens
+                (Input::Key(_), ElementState::Pressed) if self.console.is_open() => (),
+                (input, state) => self.process_input(input, state),
+            },
+        }
+    }
+
+    /// Moves the camera by the time in seconds since the last frame and the cursor to where the mouse
+    /// points, returns what the frame is rendered and edited with
+    pub fn frame(&mut self, delta_time: f32) -> (CameraProjection, MouseState) {
+        self.camera.update(delta_time);
+
+        let projection = self.camera.projection();
+        let mouse = self.mouse.state(self.tool, self.color);
+
+        self.cursor = raymarch::raycast_mouse(&self.world, &projection, mouse.coordinate());
+        self.update_selection();
+
+        (projection, mouse)
+    }
+
+    /// Mirrors the voxel edit.comp filled with w 1, cleared with w -1 or painted with w 2 for a frame
+    /// with a mouse button pressed
+    pub fn mirror_edit(&mut self, edited_voxel: Vector4<f32>) {
+        let mut edit = Edit::default();
+
+        if edited_voxel.w > 1.0 {
+            edit.paint(&mut self.world, edited_voxel.xyz(), self.color);
+        } else if edited_voxel.w > 0.0 {
+            edit.set(&mut self.world, edited_voxel.xyz(), self.color);
+        } else if edited_voxel.w < 0.0 {
+            edit.clear(&mut self.world, edited_voxel.xyz());
+        }
+
+        report_missing(edit.missing());
+        self.history.push(edit);
+    }
+
+    /// Shows the console, the active layer, the tool and the camera since there is no other UI for them yet
+    pub fn title(&self) -> String {
+        if self.console.is_open() {
+            return match self.console.message() {
+                "" => format!("{} > {}_", APP_NAME, self.console.input()),
+                message => format!("{} > {}_ ({})", APP_NAME, self.console.input(), message),
+            };
+        }
+
+        let index = self.world.active_layer();
+        let layer = &self.world.layers()[index];
+        let mut title = format!("{} - {} ({}/{})", APP_NAME, layer.name, index + 1, self.world.layers().len());
+
+        if !layer.visible {
+            title.push_str(", hidden");
+        }
+        if layer.locked {
+            title.push_str(", locked");
+        }
+
+        title.push_str(&format!(" - {} #{:06x}", self.tool.name(), self.color));
+        title.push_str(&format!(" - {} {} camera", self.camera.projection_mode().name(), self.camera.mode().name()));
+
+        title
+    }
+
+    fn undo(&mut self) {
+        if let Some(missing) = self.history.undo(&mut self.world) {
+            report_missing(missing);
+            self.changes.world = true;
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(missing) = self.history.redo(&mut self.world) {
+            report_missing(missing);
+            self.changes.world = true;
+        }
+    }
+
+    // Selection spans from the voxel under the cursor when dragging started to the one under it now
+    fn update_selection(&mut self) {
+        if !self.selecting {
+            return;
+        }
+
+        let Some(voxel) = selection::cursor_voxel(&self.world, &self.cursor) else {
+            return;
+        };
+
+        let start = *self.selection_start.get_or_insert(voxel);
+        self.selection = Some(Selection::new(start, voxel));
+    }
+
+    // Selection operations edit the CPU copy of the world, which is then uploaded whole
+    fn commit(&mut self, edit: Edit) {
+        report_missing(edit.missing());
+
+        if !edit.is_empty() {
+            self.changes.world = true;
+            self.history.push(edit);
+        }
+    }
+
+    fn fill_selection(&mut self) {
+        if let Some(selection) = self.selection {
+            let mut edit = Edit::default();
+            selection.fill(&mut self.world, &mut edit, self.color);
+            self.commit(edit);
+        }
+    }
+
+    fn clear_selection(&mut self) {
+        if let Some(selection) = self.selection {
+            let mut edit = Edit::default();
+            selection.clear(&mut self.world, &mut edit);
+            self.commit(edit);
+        }
+    }
+
+    fn move_selection(&mut self, offset: Vector3<i32>) {
+        if let Some(selection) = &mut self.selection {
+            let mut edit = Edit::default();
+            selection.move_by(&mut self.world, &mut edit, offset);
+            self.commit(edit);
+        }
+    }
+
+    fn copy_selection(&mut self) {
+        if let Some(selection) = self.selection {
+            self.clipboard = selection.copy(&self.world);
+        }
+    }
+
+    // Copy is placed the same way a single voxel would be
+    fn paste(&mut self) {
+        if self.cursor.normal.xyz() != Vector3::zeros() {
+            let lower = self.cursor.pos.xyz() + self.cursor.normal.xyz().cast::<f32>();
+            let mut edit = Edit::default();
+
+            self.clipboard.paste(&mut self.world, &mut edit, lower);
+            self.commit(edit);
+        }
+    }
+
+    // Path is typed into the console, which refuses to overwrite a file unless asked to
+    fn export_selection(&mut self) {
+        if self.selection.is_some() {
+            self.console.prompt("export ");
+            self.changes.title = true;
+        }
+    }
+
+    fn toggle_console(&mut self) {
+        self.console.toggle();
+        self.changes.title = true;
+    }
+
+    fn type_character(&mut self, character: char) {
+        self.console.push(character);
+        self.changes.title = true;
+    }
+
+    fn erase_character(&mut self) {
+        self.console.backspace();
+        self.changes.title = true;
+    }
+
+    fn execute_command(&mut self) {
+        let line = self.console.submit();
+        let mut edit = Edit::default();
+        let size = (self.window_size.width, self.window_size.height);
+
+        let result = console::parse(&line)
+            .and_then(|command| console::execute(command, &mut self.world, &mut self.camera, self.selection.as_ref(), &mut edit, &mut self.color, size));
+        let message = result.unwrap_or_else(|error| error);
+
+        self.console.set_message(message);
+        self.commit(edit);
+        self.changes.title = true;
+    }
+
+    fn select_tool(&mut self, tool: Tool) {
+        self.tool = tool;
+        self.changes.title = true;
+    }
+
+    // Takes the color of the voxel under the cursor from the active layer, or from the visible
+    // layer it's shown from. Voxels of the instances have the default color and the ground has none.
+    fn pick_color(&mut self) {
+        let pos = self.cursor.pos.xyz();
+        let mut layers = std::iter::once(self.world.active_layer())
+            .chain((0..self.world.layers().len()).filter(|index| self.world.layers()[*index].visible));
+
+        // Zero normal means there is no cursor
+        let color = match self.cursor.normal.xyz() == Vector3::zeros() {
+            true => None,
+            false if self.cursor.instance >= 0 => Some(DEFAULT_COLOR),
+            false => layers.find(|layer| self.world.contains(*layer, pos)).map(|layer| self.world.color(layer, pos)),
+        };
+
+        if let Some(color) = color {
+            self.color = color;
+            self.changes.title = true;
+        }
+    }
+
+    // New layer becomes the active one
+    fn add_layer(&mut self) {
+        let name = format!("Layer {}", self.world.layers().len() + 1);
+
+        if let Some(index) = self.world.add_layer(Layer::new(&name)) {
+            self.world.set_active_layer(index);
+            self.layers_changed();
+        }
+    }
+
+    fn select_layer(&mut self, index: usize) {
+        if index < self.world.layers().len() {
+            self.world.set_active_layer(index);
+            self.layers_changed();
+        }
+    }
+
+    fn toggle_visible(&mut self) {
+        let layer = self.world.layer_mut(self.world.active_layer());
+        layer.visible = !layer.visible;
+
+        self.layers_changed();
+    }
+
+    fn toggle_locked(&mut self) {
+        let layer = self.world.layer_mut(self.world.active_layer());
+        layer.locked = !layer.locked;
+
+        self.layers_changed();
+    }
+
+    // Layer state takes only a few words of the world buffer, but it's uploaded whole like after undo
+    fn layers_changed(&mut self) {
+        self.changes.world = true;
+        self.changes.title = true;
+    }
+
+    fn toggle_camera_mode(&mut self) {
+        self.camera.toggle_mode();
+        self.changes.title = true;
+    }
+
+    // Empty world is framed like a single chunk at the origin
+    fn preset_view(&mut self, view: PresetView) {
+        let chunk_size = self.world.chunk_size() as f32;
+        let (lower, upper) = self.world.bounds().unwrap_or((Vector3::zeros(), Vector3::repeat(chunk_size)));
+
+        self.camera.frame(view, lower, upper);
+    }
+
+    fn toggle_projection(&mut self) {
+        self.camera.toggle_projection();
+        self.changes.title = true;
+    }
+
+    // Runs the actions the input starts or stops
+    fn process_input(&mut self, input: Input, state: ElementState) {
+        let actions = match state {
+            ElementState::Pressed => self.input_map.press(input, self.modifiers).into_iter().collect(),
+            ElementState::Released => self.input_map.release(input),
+        };
+
+        for action in actions {
+            self.process_action(action, state);
+        }
+    }
+
+    fn process_action(&mut self, action: Action, state: ElementState) {
+        // Held actions also hear about the release
+        match action {
+            Action::MoveForward | Action::MoveBackward | Action::MoveLeft | Action::MoveRight | Action::MoveUp
+                | Action::MoveDown | Action::RotateCamera | Action::PanCamera => self.camera.process_action(action, state),
+            // Eyedropper doesn't edit, so it picks right away instead of leaving the click to the shaders
+            Action::UseTool if self.tool == Tool::Eyedropper => {
+                if state == ElementState::Pressed {
+                    self.pick_color();
+                }
+            },
+            Action::UseTool | Action::Erase => self.mouse.process_action(action, state),
+            Action::Select => {
+                self.selecting = state == ElementState::Pressed;
+                self.selection_start = None;
+            },
+            _ if state == ElementState::Released => (),
+            Action::SelectTool(tool) => self.select_tool(tool),
+            Action::ToggleCameraMode => self.toggle_camera_mode(),
+            Action::ToggleProjection => self.toggle_projection(),
+            Action::PresetView(view) => self.preset_view(view),
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::Copy => self.copy_selection(),
+            Action::Paste => self.paste(),
+            Action::ExportSelection => self.export_selection(),
+            Action::Deselect => self.selection = None,
+            Action::FillSelection => self.fill_selection(),
+            Action::ClearSelection => self.clear_selection(),
+            Action::MoveSelection(direction) => self.move_selection(match direction {
+                Direction::Left => Vector3::new(-1, 0, 0),
+                Direction::Right => Vector3::new(1, 0, 0),
+                Direction::Backward => Vector3::new(0, -1, 0),
+                Direction::Forward => Vector3::new(0, 1, 0),
+                Direction::Down => Vector3::new(0, 0, -1),
+                Direction::Up => Vector3::new(0, 0, 1),
+            }),
+            Action::AddLayer => self.add_layer(),
+            Action::ToggleVisible => self.toggle_visible(),
+            Action::ToggleLocked => self.toggle_locked(),
+            Action::SelectLayer(index) => self.select_layer(index),
+            Action::Quit => self.changes.quit = true,
+        }
+    }
+}
+
+fn report_missing(missing: usize) {
+    if missing > 0 {
+        eprintln!("{} voxels didn't fit into the {} chunks a scene shares with its models", missing, MAX_OCTREES);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,388 @@
+use std::{
+    fs::File,
+    path::Path,
+    io::{
+        self,
+        Read,
+        Write,
+        BufReader,
+        BufWriter
+    },
+};
+use nalgebra::Vector3;
+use super::{
+    camera::Bookmark,
+    octree::{
+        Octree,
+        MEMORY_SIZE,
+        STACK_SIZE
+    },
+    world::{
+        World,
+        Layer,
+        MAX_LAYERS
+    },
+};
+
+// Native scene file, all values are little endian u32 unless noted otherwise:
+// magic, version, chunk depth, layer count, active layer, then every layer as its name length,
+// name in UTF-8 bytes, flags (1 visible, 2 locked), chunk count and the chunks. Chunk is stored as
+// its x, y and z coordinate as i32 followed by the octree, which is stored as free address,
+// allocated descriptors and the root descriptor. Layers are followed by the bookmark count and the
+// bookmarks, each as its name length, name in UTF-8 bytes, look from and look at as f32 bits.
+// Bookmarks are followed by the count of painted voxels and for each its layer, x, y and z position
+// as i32 and color as 0xRRGGBB.
+const MAGIC: &[u8; 4] = b"VXED";
+const VERSION: u32 = 1;
+
+const VISIBLE_FLAG: u32 = 1;
+const LOCKED_FLAG: u32 = 2;
+
+pub fn save(path: &Path, world: &World) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+
+    file.write_all(MAGIC)?;
+    write_u32(&mut file, VERSION)?;
+    write_u32(&mut file, world.chunk_depth())?;
+    write_u32(&mut file, world.layers().len() as u32)?;
+    write_u32(&mut file, world.active_layer() as u32)?;
+
+    for (index, layer) in world.layers().iter().enumerate() {
+        let chunks = world.chunks().iter().filter(|chunk| chunk.layer == index).collect::<Vec<_>>();
+        let mut flags = 0;
+
+        if layer.visible {
+            flags |= VISIBLE_FLAG;
+        }
+        if layer.locked {
+            flags |= LOCKED_FLAG;
+        }
+
+        write_string(&mut file, &layer.name)?;
+        write_u32(&mut file, flags)?;
+        write_u32(&mut file, chunks.len() as u32)?;
+
+        for chunk in chunks {
+            for coordinate in chunk.coordinate.iter() {
+                write_u32(&mut file, *coordinate as u32)?;
+            }
+
+            write_octree(&mut file, &chunk.octree)?;
+        }
+    }
+
+    write_u32(&mut file, world.bookmarks().len() as u32)?;
+
+    for bookmark in world.bookmarks() {
+        write_string(&mut file, &bookmark.name)?;
+        write_vector(&mut file, bookmark.look_from)?;
+        write_vector(&mut file, bookmark.look_at)?;
+    }
+
+    write_u32(&mut file, world.colors().count() as u32)?;
+
+    for (pos, layer, color) in world.colors() {
+        write_u32(&mut file, layer as u32)?;
+
+        for coordinate in pos.iter() {
+            write_u32(&mut file, *coordinate as u32)?;
+        }
+
+        write_u32(&mut file, color)?;
+    }
+
+    file.flush()
+}
+
+pub fn load(path: &Path) -> io::Result<World> {
+    let mut file = BufReader::new(File::open(path)?);
+
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)?;
+
+    if &magic != MAGIC {
+        return Err(invalid_data("not a scene file"));
+    }
+
+    let version = read_u32(&mut file)?;
+
+    if version != VERSION {
+        return Err(invalid_data(&format!("unsupported scene file version {}", version)));
+    }
+
+    let depth = read_u32(&mut file)?;
+
+    if depth < 1 || depth >= STACK_SIZE as u32 {
+        return Err(invalid_data(&format!("invalid octree depth {}", depth)));
+    }
+
+    let layer_count = read_u32(&mut file)? as usize;
+    let active_layer = read_u32(&mut file)? as usize;
+
+    if layer_count == 0 || layer_count > MAX_LAYERS {
+        return Err(invalid_data(&format!("invalid layer count {}", layer_count)));
+    }
+
+    if active_layer >= layer_count {
+        return Err(invalid_data(&format!("active layer {} doesn't exist", active_layer)));
+    }
+
+    let mut layers = Vec::with_capacity(layer_count);
+    let mut chunks = Vec::new();
+
+    for index in 0..layer_count {
+        let name = read_string(&mut file)?;
+        let flags = read_u32(&mut file)?;
+
+        layers.push(Layer {
+            name,
+            visible: flags & VISIBLE_FLAG != 0,
+            locked: flags & LOCKED_FLAG != 0,
+        });
+
+        for _ in 0..read_u32(&mut file)? {
+            let x = read_u32(&mut file)? as i32;
+            let y = read_u32(&mut file)? as i32;
+            let z = read_u32(&mut file)? as i32;
+
+            chunks.push((Vector3::new(x, y, z), index, read_octree(&mut file, depth)?));
+        }
+    }
+
+    let mut world = World::with_layers(depth, layers);
+    world.set_active_layer(active_layer);
+
+    for (coordinate, layer, octree) in chunks {
+        if !world.add_chunk(coordinate, layer, octree) {
+            return Err(invalid_data("too many chunks or a chunk is stored twice"));
+        }
+    }
+
+    for _ in 0..read_u32(&mut file)? {
+        world.set_bookmark(Bookmark {
+            name: read_string(&mut file)?,
+            look_from: read_vector(&mut file)?,
+            look_at: read_vector(&mut file)?,
+        });
+    }
+
+    for _ in 0..read_u32(&mut file)? {
+        let layer = read_u32(&mut file)? as usize;
+        let x = read_u32(&mut file)? as i32;
+        let y = read_u32(&mut file)? as i32;
+        let z = read_u32(&mut file)? as i32;
+        let color = read_u32(&mut file)?;
+
+        if layer >= layer_count {
+            return Err(invalid_data(&format!("painted voxel of layer {} which doesn't exist", layer)));
+        }
+
+        if !world.set_color(layer, Vector3::new(x, y, z).cast::<f32>(), color & 0xFFFFFF) {
+            return Err(invalid_data("too many painted voxels"));
+        }
+    }
+
+    Ok(world)
+}
+
+fn write_octree(file: &mut impl Write, octree: &Octree) -> io::Result<()> {
+    write_u32(file, octree.free_address)?;
+
+    // Children of the root take the first 8 descriptors, every allocation after them takes 8 more
+    for descriptor in &octree.descriptors[..octree.free_address as usize + 8] {
+        write_u32(file, *descriptor)?;
+    }
+
+    write_u32(file, octree.descriptors[MEMORY_SIZE - 1])
+}
+
+fn read_octree(file: &mut impl Read, depth: u32) -> io::Result<Octree> {
+    let mut octree = Octree::new(depth, Vec::new());
+    octree.free_address = read_u32(file)?;
+
+    if octree.free_address as usize + 8 >= MEMORY_SIZE {
+        return Err(invalid_data("octree doesn't fit into memory"));
+    }
+
+    for descriptor in &mut octree.descriptors[..octree.free_address as usize + 8] {
+        *descriptor = read_u32(file)?;
+    }
+
+    octree.descriptors[MEMORY_SIZE - 1] = read_u32(file)?;
+
+    // Children have to be within the allocated blocks, which also keeps them inside the memory
+    let addresses = (0..octree.free_address as usize + 8).chain([MEMORY_SIZE - 1]);
+
+    if let Some(pointer) = addresses.map(|address| octree.descriptors[address] >> 8).find(|pointer| *pointer > octree.free_address) {
+        return Err(invalid_data(&format!("child pointer {} is outside the allocated octree memory", pointer)));
+    }
+
+    Ok(octree)
+}
+
+fn write_u32(file: &mut impl Write, value: u32) -> io::Result<()> {
+    file.write_all(&value.to_le_bytes())
+}
+
+pub fn read_u32(file: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    file.read_exact(&mut bytes)?;
+
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn write_string(file: &mut impl Write, value: &str) -> io::Result<()> {
+    write_u32(file, value.len() as u32)?;
+    file.write_all(value.as_bytes())
+}
+
+fn read_string(file: &mut impl Read) -> io::Result<String> {
+    let mut bytes = vec![0; read_u32(file)? as usize];
+    file.read_exact(&mut bytes)?;
+
+    String::from_utf8(bytes).map_err(|_| invalid_data("name is not valid UTF-8"))
+}
+
+fn write_vector(file: &mut impl Write, value: Vector3<f32>) -> io::Result<()> {
+    for component in value.iter() {
+        write_u32(file, component.to_bits())?;
+    }
+
+    Ok(())
+}
+
+fn read_vector(file: &mut impl Read) -> io::Result<Vector3<f32>> {
+    let x = f32::from_bits(read_u32(file)?);
+    let y = f32::from_bits(read_u32(file)?);
+    let z = f32::from_bits(read_u32(file)?);
+
+    Ok(Vector3::new(x, y, z))
+}
+
+pub fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // Scene file in the temporary directory which is removed once the test is done with it
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!("voxel_editor_{}_{}.vxed", name, std::process::id())))
+        }
+
+        fn with_values(name: &str, values: &[u32]) -> Self {
+            let file = Self::new(name);
+            let bytes = values.iter().flat_map(|value| value.to_le_bytes()).collect::<Vec<_>>();
+
+            std::fs::write(&file.0, [MAGIC.as_slice(), &bytes].concat()).unwrap();
+            file
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    // Scene with a single visible layer holding the octree as its chunk at (1, 1, 1) and nothing else
+    fn single_chunk(depth: u32, octree: &[u32]) -> Vec<u32> {
+        [&[VERSION, depth, 1, 0, 0, VISIBLE_FLAG, 1, 1, 1, 1], octree, &[0, 0]].concat()
+    }
+
+    fn sorted_voxels(world: &World, layer: usize) -> Vec<Vector3<f32>> {
+        let mut voxels = world.voxels(layer).collect::<Vec<_>>();
+        voxels.sort_by(|a, b| a.as_slice().partial_cmp(b.as_slice()).unwrap());
+
+        voxels
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let mut world = World::new(3);
+        let hidden = world.add_layer(Layer::new("Hidden")).unwrap();
+        world.layer_mut(hidden).visible = false;
+        world.layer_mut(0).locked = true;
+        world.set_active_layer(hidden);
+
+        for pos in [Vector3::new(1.0, 2.0, 3.0), Vector3::new(-5.0, 9.0, 0.0), Vector3::new(7.0, 7.0, 7.0)] {
+            assert!(world.insert(0, pos));
+        }
+        assert!(world.insert(hidden, Vector3::new(20.0, -20.0, 4.0)));
+        assert!(world.set_color(0, Vector3::new(1.0, 2.0, 3.0), 0x123456));
+        world.set_bookmark(Bookmark {
+            name: String::from("Top"),
+            look_from: Vector3::new(1.0, 2.0, 30.0),
+            look_at: Vector3::new(1.5, 2.5, 0.0),
+        });
+
+        let file = TempFile::new("round_trip");
+        save(&file.0, &world).unwrap();
+        let loaded = load(&file.0).unwrap();
+
+        assert_eq!(loaded.chunk_depth(), 3);
+        assert_eq!(loaded.active_layer(), hidden);
+        assert_eq!(loaded.chunks().len(), world.chunks().len());
+
+        for (layer, loaded_layer) in world.layers().iter().zip(loaded.layers()) {
+            assert_eq!((&layer.name, layer.visible, layer.locked), (&loaded_layer.name, loaded_layer.visible, loaded_layer.locked));
+        }
+
+        for layer in 0..2 {
+            assert_eq!(sorted_voxels(&world, layer), sorted_voxels(&loaded, layer));
+        }
+
+        assert_eq!(loaded.color(0, Vector3::new(1.0, 2.0, 3.0)), 0x123456);
+        assert_eq!(loaded.colors().count(), 1);
+
+        let bookmark = loaded.find_bookmark("Top").unwrap();
+        assert_eq!((bookmark.look_from, bookmark.look_at), (Vector3::new(1.0, 2.0, 30.0), Vector3::new(1.5, 2.5, 0.0)));
+    }
+
+    #[test]
+    fn load_reads_chunks_in_the_documented_layout() {
+        // Depth 2 chunk whose octree coordinates match the world, with the first voxel of the root's first child
+        let file = TempFile::with_values("layout", &single_chunk(2, &[0, 1, 0, 0, 0, 0, 0, 0, 0, 1]));
+        let world = load(&file.0).unwrap();
+
+        assert_eq!(world.layers().len(), 1);
+        assert_eq!(sorted_voxels(&world, 0), [Vector3::new(4.0, 4.0, 4.0)]);
+
+        // Depth 3 with the child pointer 8 in the upper 24 bits of the root's first child
+        let file = TempFile::with_values("layout_depth_3", &single_chunk(3, &[8, 1 | 8 << 8, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1]));
+        let world = load(&file.0).unwrap();
+
+        assert_eq!(sorted_voxels(&world, 0), [Vector3::new(8.0, 8.0, 8.0)]);
+    }
+
+    #[test]
+    fn load_rejects_other_versions() {
+        for version in [0, 2] {
+            let mut values = single_chunk(2, &[0, 1, 0, 0, 0, 0, 0, 0, 0, 1]);
+            values[0] = version;
+            let file = TempFile::with_values("version", &values);
+
+            assert!(load(&file.0).is_err());
+        }
+    }
+
+    #[test]
+    fn load_rejects_out_of_range_pointers() {
+        for pointer in [16, 1 << 20] {
+            let file = TempFile::with_values("pointer", &single_chunk(3, &[8, 1 | pointer << 8, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1]));
+
+            assert!(load(&file.0).is_err());
+        }
+
+        // Free address past the memory
+        let file = TempFile::with_values("free_address", &single_chunk(3, &[MEMORY_SIZE as u32]));
+
+        assert!(load(&file.0).is_err());
+    }
+}
\ No newline at end of file
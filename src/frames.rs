@@ -0,0 +1,166 @@
+use std::{
+    fs,
+    path::Path,
+    f32::consts::TAU,
+};
+use nalgebra::{
+    Vector3,
+    Rotation3
+};
+use super::{
+    raymarch,
+    headless,
+    world::World,
+    cursor::Cursor,
+    mouse::MouseState,
+    camera::{
+        Camera,
+        Bookmark
+    },
+};
+
+/// Where the camera goes during a frame sequence
+pub enum CameraPath {
+    /// Full turn around the vertical axis through the point the camera looks at
+    Turntable,
+    /// Goes through the bookmarks in order along a Catmull-Rom spline
+    Keyframes(Vec<Bookmark>),
+}
+
+impl CameraPath {
+    /// Keyframes are the world's bookmarks with given names
+    pub fn keyframes(world: &World, names: &[String]) -> Result<Self, String> {
+        if names.len() < 2 {
+            return Err(String::from("Camera path needs at least two bookmarks"));
+        }
+
+        names.iter()
+            .map(|name| world.find_bookmark(name).cloned().ok_or_else(|| format!("No bookmark named '{}'", name)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(CameraPath::Keyframes)
+    }
+
+    // Turntable stops a step before the start so the sequence loops, keyframes end at the last one
+    fn bookmark(&self, start: &Bookmark, frame: u32, frame_count: u32) -> Bookmark {
+        match self {
+            CameraPath::Turntable => {
+                let rotation = Rotation3::from_axis_angle(&Vector3::z_axis(), TAU * frame as f32 / frame_count as f32);
+
+                Bookmark {
+                    name: String::new(),
+                    look_from: start.look_at + rotation * (start.look_from - start.look_at),
+                    look_at: start.look_at,
+                }
+            },
+            CameraPath::Keyframes(keyframes) => {
+                let segments = keyframes.len() - 1;
+                let t = frame as f32 * segments as f32 / (frame_count - 1).max(1) as f32;
+                let segment = (t as usize).min(segments - 1);
+                let t = t - segment as f32;
+
+                // Ends are repeated, so the spline starts and stops at the first and last keyframe
+                let points = [segment.saturating_sub(1), segment, segment + 1, (segment + 2).min(segments)]
+                    .map(|index| &keyframes[index]);
+
+                Bookmark {
+                    name: String::new(),
+                    look_from: catmull_rom(points.map(|keyframe| keyframe.look_from), t),
+                    look_at: catmull_rom(points.map(|keyframe| keyframe.look_at), t),
+                }
+            },
+        }
+    }
+}
+
+/// Renders the frames with the CPU renderer into numbered PNG images in the directory, the camera
+/// gives the projection and the start of a turntable
+pub fn export(
+    world: &World, 
+    camera: &Camera, 
+    path: &CameraPath, 
+    frame_count: u32, 
+    width: u32, 
+    height: u32, 
+    directory: &Path
+) -> Result<(), String> {
+    fs::create_dir_all(directory).map_err(|error| format!("Couldn't create {}: {}", directory.display(), error))?;
+
+    let start = camera.bookmark("");
+
+    for frame in 0..frame_count {
+        let frame_camera = camera.at(&path.bookmark(&start, frame, frame_count));
+        let pixels = raymarch::render(world, &frame_camera.projection(), &Cursor::default(), &MouseState::default(), None, width, height);
+        let file = directory.join(format!("frame_{:04}.png", frame));
+
+        headless::write_png(&file, width, height, &pixels)
+            .map_err(|error| format!("Couldn't write {}: {}", file.display(), error))?;
+    }
+
+    Ok(())
+}
+
+fn catmull_rom([p0, p1, p2, p3]: [Vector3<f32>; 4], t: f32) -> Vector3<f32> {
+    let (t2, t3) = (t * t, t * t * t);
+
+    (p1 * 2.0 
+        + (p2 - p0) * t 
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2 
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3) * 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(name: &str, look_from: [f32; 3], look_at: [f32; 3]) -> Bookmark {
+        Bookmark {
+            name: String::from(name),
+            look_from: Vector3::from(look_from),
+            look_at: Vector3::from(look_at),
+        }
+    }
+
+    fn assert_at(actual: &Bookmark, expected: &Bookmark) {
+        assert!((actual.look_from - expected.look_from).norm() < 1e-5, "{:?}", actual.look_from);
+        assert!((actual.look_at - expected.look_at).norm() < 1e-5, "{:?}", actual.look_at);
+    }
+
+    #[test]
+    fn keyframes_start_and_end_at_the_first_and_last_bookmark() {
+        let keyframes = vec![
+            bookmark("a", [0.0, -10.0, 2.0], [0.0, 0.0, 0.0]),
+            bookmark("b", [10.0, 0.0, 4.0], [1.0, 1.0, 1.0]),
+            bookmark("c", [0.0, 10.0, 6.0], [2.0, 0.0, 2.0]),
+        ];
+        let path = CameraPath::Keyframes(keyframes.clone());
+        let start = bookmark("", [5.0, 5.0, 5.0], [0.0, 0.0, 0.0]);
+
+        assert_at(&path.bookmark(&start, 0, 5), &keyframes[0]);
+        assert_at(&path.bookmark(&start, 2, 5), &keyframes[1]);
+        assert_at(&path.bookmark(&start, 4, 5), &keyframes[2]);
+
+        // Single frame is the first keyframe instead of dividing by zero
+        assert_at(&path.bookmark(&start, 0, 1), &keyframes[0]);
+    }
+
+    #[test]
+    fn turntable_keeps_its_distance_and_loops() {
+        let start = bookmark("", [0.0, -10.0, 3.0], [1.0, 2.0, 1.0]);
+        let frame_count = 8;
+        let distance = (start.look_from - start.look_at).norm();
+
+        for frame in 0..frame_count {
+            let bookmark = CameraPath::Turntable.bookmark(&start, frame, frame_count);
+
+            assert!(bookmark.look_at == start.look_at);
+            assert!(((bookmark.look_from - bookmark.look_at).norm() - distance).abs() < 1e-4);
+            assert!((bookmark.look_from.z - start.look_from.z).abs() < 1e-5);
+        }
+
+        // Last frame is a step before the start, so the first one isn't shown twice when the sequence loops
+        assert_at(&CameraPath::Turntable.bookmark(&start, 0, frame_count), &start);
+        let last = CameraPath::Turntable.bookmark(&start, frame_count - 1, frame_count);
+        let step = (TAU / frame_count as f32 * 0.5).sin() * 2.0 * (start.look_from - start.look_at).xy().norm();
+        assert!(((last.look_from - start.look_from).norm() - step).abs() < 1e-3);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,441 @@
+use ash::vk;
+use std::{
+    fs::File,
+    path::Path,
+    io::{
+        self,
+        BufWriter
+    },
+};
+use nalgebra::Vector3;
+use super::{
+    format,
+    vox,
+    sdf,
+    mesh,
+    raymarch,
+    editor::Editor,
+    recording::{
+        self,
+        Record
+    },
+    dag::Dag,
+    world::World,
+    cursor::Cursor,
+    mouse::MouseState,
+    camera::Camera,
+    frames::{
+        self,
+        CameraPath
+    },
+    cli::{
+        RenderOptions,
+        FramesOptions,
+        ShapeOptions,
+        TerrainOptions
+    },
+};
+
+pub fn render(options: &RenderOptions) -> Result<(), String> {
+    let (world, camera) = load(options)?;
+    let pixels = raymarch::render(&world, &camera.projection(), &Cursor::default(), &MouseState::default(), None, options.width, options.height);
+
+    write_png(&options.output, options.width, options.height, &pixels)
+        .map_err(|error| format!("Couldn't write {}: {}", options.output.display(), error))
+}
+
+pub fn frames(options: &FramesOptions) -> Result<(), String> {
+    let render = &options.render;
+    let (world, camera) = load(render)?;
+
+    let path = match options.keyframes.is_empty() {
+        true => CameraPath::Turntable,
+        false => CameraPath::keyframes(&world, &options.keyframes)?,
+    };
+
+    frames::export(&world, &camera, &path, options.frame_count, render.width, render.height, &render.output)?;
+    println!("Wrote {} frames to {}", options.frame_count, render.output.display());
+
+    Ok(())
+}
+
+/// Replays a recording against the editor like the window would, but with the CPU renderer, and
+/// writes the last frame at the recorded window size
+pub fn replay(editor: &mut Editor, recording: &Path, output: &Path) -> Result<(), String> {
+    let records = recording::load(recording).map_err(|error| format!("Couldn't load {}: {}", recording.display(), error))?;
+    let mut cursor = Cursor::default(); // What the cursor buffer would hold
+    let mut projection = editor.camera().projection();
+    let mut mouse = MouseState::default();
+    let mut last_frame = 0.0;
+    let mut frame_count = 0;
+
+    for (time, record) in records {
+        match record {
+            Record::Event(event) => {
+                editor.handle(event);
+
+                if editor.take_changes().quit {
+                    break;
+                }
+            },
+            Record::Frame => {
+                (projection, mouse) = editor.frame((time - last_frame) as f32);
+                last_frame = time;
+                frame_count += 1;
+
+                // Edit pass runs before the mouse raycast pass, so it edits at the cursor of the last frame
+                if mouse.left_button == vk::TRUE || mouse.right_button == vk::TRUE {
+                    editor.mirror_edit(raymarch::edited_voxel(editor.world(), &cursor, &mouse));
+                }
+
+                cursor = raymarch::raycast_mouse(editor.world(), &projection, mouse.coordinate());
+            },
+        }
+    }
+
+    let size = editor.window_size();
+    let pixels = raymarch::render(editor.world(), &projection, &cursor, &mouse, editor.selection(), size.width, size.height);
+
+    write_png(output, size.width, size.height, &pixels).map_err(|error| format!("Couldn't write {}: {}", output.display(), error))?;
+    println!("Replayed {} frames", frame_count);
+
+    Ok(())
+}
+
+// Scene and the camera to render it with
+fn load(options: &RenderOptions) -> Result<(World, Camera), String> {
+    let mut world = format::load(&options.scene)
+        .map_err(|error| format!("Couldn't load {}: {}", options.scene.display(), error))?;
+
+    let (look_from, look_at) = default_view(&world);
+    let mut camera = Camera::new(
+        options.look_from.unwrap_or(look_from),
+        options.look_at.unwrap_or(look_at),
+    );
+    camera.set_field_of_view(options.field_of_view);
+    camera.set_aspect_ratio(options.width as f32 / options.height as f32);
+
+    if options.orthographic {
+        camera.toggle_projection();
+    }
+
+    // Chunks are compressed one by one, so each of them reports how well it compressed
+    if options.dag {
+        for chunk in world.chunks_mut() {
+            let dag = Dag::new(&chunk.octree);
+            let coordinate = chunk.coordinate;
+
+            println!(
+                "Compressed chunk ({}, {}, {}) of layer {} from {} descriptors into {} ({:.2}x)", 
+                coordinate.x, 
+                coordinate.y, 
+                coordinate.z, 
+                chunk.layer + 1, 
+                dag.original_size(), 
+                dag.size(), 
+                dag.compression_ratio()
+            );
+
+            chunk.octree = dag.octree().clone();
+        }
+    }
+
+    Ok((world, camera))
+}
+
+/// Reads a scene file or a MagicaVoxel file and writes it as a scene file or an OBJ mesh
+pub fn convert(input: &Path, output: &Path) -> Result<(), String> {
+    let load_error = |error: io::Error| format!("Couldn't load {}: {}", input.display(), error);
+
+    if extension(input).as_deref() != Some("vox") {
+        return write(output, &format::load(input).map_err(load_error)?);
+    }
+
+    let voxels = vox::load(input).map_err(load_error)?;
+
+    // Mesh is written straight from the voxels, so it takes models no matter how many chunks they span
+    match extension(output).as_deref() {
+        Some("obj") => mesh::write_obj(output, &voxels).map_err(|error| format!("Couldn't write {}: {}", output.display(), error)),
+        _ => write(output, &vox::world(&voxels).map_err(load_error)?),
+    }
+}
+
+/// Voxelizes a shape expression centered in an octree and writes it like convert does
+pub fn shape(options: &ShapeOptions) -> Result<(), String> {
+    let center = Vector3::repeat(1.5 * (1 << options.depth) as f32);
+    let octree = sdf::parse(&options.expression)?.translate(center).voxelize(options.depth)?;
+
+    write(&options.output, &World::from_octree(octree))
+}
+
+pub fn terrain(options: &TerrainOptions) -> Result<(), String> {
+    let octree = options.terrain.generate(options.depth)?;
+
+    write(&options.output, &World::from_octree(octree))
+}
+
+// Scene file or an OBJ mesh depending on the extension
+fn write(output: &Path, world: &World) -> Result<(), String> {
+    // Mesh has what the editor shows, so hidden layers are left out of it
+    let result = match extension(output).as_deref() {
+        Some("obj") => mesh::write_obj(output, &visible_voxels(world)),
+        Some("vox") => return Err(String::from("Writing MagicaVoxel files isn't supported")),
+        _ => format::save(output, world),
+    };
+
+    result.map_err(|error| format!("Couldn't write {}: {}", output.display(), error))
+}
+
+pub fn info(path: &Path) -> Result<(), String> {
+    let world = format::load(path)
+        .map_err(|error| format!("Couldn't load {}: {}", path.display(), error))?;
+
+    println!("{}", describe(&world));
+
+    Ok(())
+}
+
+// Sizes of the world and what its layers and instances hold, one line each
+fn describe(world: &World) -> String {
+    let scene = world.scene();
+
+    let mut voxels = Vec::new();
+    let mut layers = Vec::new();
+
+    for (index, layer) in world.layers().iter().enumerate() {
+        let count = voxels.len();
+        voxels.extend(world.voxels(index));
+
+        let chunks = world.chunks().iter().filter(|chunk| chunk.layer == index).count();
+        let hidden = if layer.visible { "" } else { ", hidden" };
+        let locked = if layer.locked { ", locked" } else { "" };
+
+        layers.push(format!("    {}{}{}: {} voxels in {} chunks", layer.name, hidden, locked, voxels.len() - count, chunks));
+    }
+
+    let layer_voxels = voxels.len();
+    voxels.extend(scene.instances().iter().flat_map(|instance| scene.voxels(instance)));
+
+    // Models are stored once no matter how many instances place them
+    let nodes = world.chunks().iter().map(|chunk| chunk.octree.node_count()).sum::<usize>()
+        + scene.models().iter().map(|model| model.node_count()).sum::<usize>();
+
+    let bounds = match world.bounds() {
+        Some((lower, upper)) => format!(
+            "Bounds: ({}, {}, {}) to ({}, {}, {})",
+            lower.x, lower.y, lower.z, upper.x, upper.y, upper.z
+        ),
+        None => String::from("Bounds: empty"),
+    };
+
+    [
+        format!("Chunk depth: {} ({} voxels along each axis)", world.chunk_depth(), world.chunk_size()),
+        bounds,
+        format!("Voxels: {}", voxels.len()),
+        format!("Nodes: {}", nodes),
+        format!("Layers:\n{}", layers.join("\n")),
+        format!(
+            "Instances: {} of {} models with {} voxels",
+            scene.instances().len(),
+            scene.models().len(),
+            voxels.len() - layer_voxels
+        ),
+    ].join("\n")
+}
+
+pub fn write_png(path: &Path, width: u32, height: u32, pixels: &[u8]) -> Result<(), png::EncodingError> {
+    let file = BufWriter::new(File::create(path)?);
+
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()
+}
+
+// Voxels of the visible layers and of the instances
+fn visible_voxels(world: &World) -> Vec<Vector3<f32>> {
+    let scene = world.scene();
+
+    world.layers()
+        .iter()
+        .enumerate()
+        .filter(|(_, layer)| layer.visible)
+        .flat_map(|(index, _)| world.voxels(index))
+        .chain(scene.instances().iter().flat_map(|instance| scene.voxels(instance)))
+        .collect()
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension().map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
+/// Looks at the center of the chunks in use from the front and slightly above
+pub fn default_view(world: &World) -> (Vector3<f32>, Vector3<f32>) {
+    let chunk_size = world.chunk_size() as f32;
+    let (lower, upper) = world.chunk_range().unwrap_or((Vector3::zeros(), Vector3::zeros()));
+    let lower = lower.cast::<f32>() * chunk_size;
+    let upper = upper.add_scalar(1).cast::<f32>() * chunk_size;
+    let size = (upper - lower).max();
+    let center = (lower + upper) * 0.5;
+    let look_from = center + Vector3::new(0.0, -1.5 * size, 0.5 * size);
+
+    (look_from, center)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::event::ModifiersState;
+    use crate::{
+        input::InputMap,
+        world::{
+            Layer,
+            DEFAULT_COLOR
+        },
+    };
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("voxel_editor_{}_{}", std::process::id(), name))
+    }
+
+    // One voxel in the visible layer and two in the hidden one
+    fn layered_world() -> World {
+        let mut world = World::new(3);
+        let hidden = world.add_layer(Layer::new("Hidden")).unwrap();
+
+        world.layer_mut(hidden).visible = false;
+        world.insert(0, Vector3::new(1.0, 2.0, 3.0));
+        world.insert(hidden, Vector3::new(20.0, 2.0, 3.0));
+        world.insert(hidden, Vector3::new(21.0, 2.0, 3.0));
+
+        world
+    }
+
+    #[test]
+    fn convert_writes_only_visible_layers_into_meshes() {
+        let (scene, mesh, copy) = (temp_path("layers.vxed"), temp_path("layers.obj"), temp_path("copy.vxed"));
+
+        format::save(&scene, &layered_world()).unwrap();
+        convert(&scene, &mesh).unwrap();
+        convert(&scene, &copy).unwrap();
+
+        let text = std::fs::read_to_string(&mesh).unwrap();
+        let copied = format::load(&copy).unwrap();
+
+        for path in [&scene, &mesh, &copy] {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        assert_eq!(text.lines().next(), Some("# 1 voxels, 6 faces"));
+        assert_eq!((copied.voxels(0).count(), copied.voxels(1).count()), (1, 2));
+    }
+
+    #[test]
+    fn describe_counts_voxels_of_every_layer() {
+        let description = describe(&layered_world());
+
+        assert!(description.contains("Chunk depth: 3 (8 voxels along each axis)"), "{}", description);
+        assert!(description.contains("Bounds: (1, 2, 3) to (22, 3, 4)"), "{}", description);
+        assert!(description.contains("Voxels: 3\n"), "{}", description);
+        assert!(description.contains("    Layer 1: 1 voxels in 1 chunks\n    Hidden, hidden: 2 voxels in 1 chunks"), "{}", description);
+        assert!(description.ends_with("Instances: 0 of 0 models with 0 voxels"), "{}", description);
+    }
+
+    // Records of opening the console, running the command and closing it again
+    fn typed(command: &str) -> String {
+        let characters = command.chars().map(|character| format!("0.02 character {}\n", character as u32)).collect::<String>();
+
+        format!(
+            "0.02 press Grave\n0.02 release Grave\n{}0.02 press Return\n0.02 release Return\n0.02 press Grave\n0.02 release Grave\n",
+            characters
+        )
+    }
+
+    // Records of a click at the cursor with frames for the edit and the cursor raycast
+    fn clicked() -> &'static str {
+        "0.04 press MouseLeft\n0.05 frame\n0.05 release MouseLeft\n0.06 frame\n"
+    }
+
+    // Editor looking at the center of the front side of a filled 3x3x3 box
+    fn editor() -> Editor {
+        let camera = Camera::new(Vector3::new(1.5, -20.0, 1.5), Vector3::repeat(1.5));
+
+        Editor::new(World::new(3), camera, InputMap::load(None).unwrap(), winit::dpi::PhysicalSize::new(320, 180))
+    }
+
+    fn replay_records(editor: &mut Editor, records: &str) {
+        let (recording, output) = (temp_path("records.txt"), temp_path("records.png"));
+        std::fs::write(&recording, format!("voxel_editor recording 1\n0 resize 64 36\n0 cursor 32 18\n0.01 frame\n{}", records)).unwrap();
+
+        let result = replay(editor, &recording, &output);
+
+        for path in [&recording, &output] {
+            let _ = std::fs::remove_file(path);
+        }
+        result.unwrap();
+    }
+
+    #[test]
+    fn replay_drives_the_editor_like_the_window() {
+        let (recording, output) = (temp_path("replay.txt"), temp_path("replay.png"));
+        let text = format!(
+            "voxel_editor recording 1\n0 resize 64 36\n0 cursor 32 18\n0.01 frame\n{}0.03 frame\n{}",
+            typed("fill 0 0 0 2 2 2"),
+            clicked()
+        );
+        std::fs::write(&recording, text).unwrap();
+
+        let mut editor = editor();
+
+        replay(&mut editor, &recording, &output).unwrap();
+
+        let written = std::fs::metadata(&output).is_ok();
+
+        for path in [&recording, &output] {
+            let _ = std::fs::remove_file(path);
+        }
+
+        // Pencil adds a voxel in front of the face of the filled box the cursor points at
+        let world = editor.world();
+
+        assert!(written);
+        assert_eq!(editor.window_size(), winit::dpi::PhysicalSize::new(64, 36));
+        assert_eq!(world.voxels(0).count(), 28);
+        assert!(world.contains(0, Vector3::new(1.0, 2.0, 1.0)));
+        assert!(world.contains(0, Vector3::new(1.0, -1.0, 1.0)));
+    }
+
+    #[test]
+    fn paint_recolors_voxels_and_the_eyedropper_picks_the_color() {
+        let mut editor = editor();
+        let front = Vector3::new(1.0, 0.0, 1.0);
+
+        replay_records(&mut editor, &format!(
+            "{}{}0.03 frame\n0.03 press G\n0.03 release G\n{}",
+            typed("fill 0 0 0 2 2 2"),
+            typed("color ff0000"),
+            clicked()
+        ));
+
+        // Only the color of the voxel under the cursor changes
+        assert_eq!(editor.world().voxels(0).count(), 27);
+        assert_eq!(editor.world().color(0, front), 0xFF0000);
+        assert_eq!(editor.world().color(0, Vector3::new(0.0, 0.0, 1.0)), DEFAULT_COLOR);
+
+        let ctrl = format!("0.07 modifiers {}\n", ModifiersState::CTRL.bits());
+        replay_records(&mut editor, &format!("{}0.07 press Z\n0.07 release Z\n0.07 modifiers 0\n", ctrl));
+        assert_eq!(editor.world().voxels(0).count(), 27);
+        assert_eq!(editor.world().color(0, front), DEFAULT_COLOR);
+
+        replay_records(&mut editor, &format!("{}0.07 press Y\n0.07 release Y\n0.07 modifiers 0\n", ctrl));
+        assert_eq!(editor.world().color(0, front), 0xFF0000);
+
+        // Eyedropper takes the painted color instead of the one the tools were using
+        replay_records(&mut editor, &format!("{}0.07 press I\n0.07 release I\n{}", typed("color 00ff00"), clicked()));
+        assert!(editor.title().contains("eyedropper #ff0000"), "{}", editor.title());
+        assert_eq!(editor.world().color(0, front), 0xFF0000);
+    }
+}
\ No newline at end of file
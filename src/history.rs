@@ -0,0 +1,269 @@
+use nalgebra::Vector3;
+use super::world::World;
+
+// Voxel before and after it was changed, as its color or None when it's empty
+#[derive(Clone, Copy)]
+struct Change {
+    pos: Vector3<f32>,
+    layer: usize,
+    before: Option<u32>,
+    after: Option<u32>,
+}
+
+impl Change {
+    // Layers might have been locked since, which doesn't stop undo and redo. Returns false if the
+    // voxel didn't fit into the world, which leaves it empty.
+    fn apply(&self, world: &mut World, state: Option<u32>) -> bool {
+        match state {
+            Some(color) if world.insert(self.layer, self.pos) => {
+                world.set_color(self.layer, self.pos, color);
+                true
+            },
+            Some(_) => false,
+            None => {
+                world.remove(self.layer, self.pos);
+                true
+            },
+        }
+    }
+}
+
+/// Group of voxel changes which are undone and redone together, like a single click or a brush stroke
+#[derive(Default)]
+pub struct Edit {
+    changes: Vec<Change>,
+    missing: usize,
+}
+
+impl Edit {
+    /// Fills the voxel in the active layer with a color, nothing happens if the layer is locked
+    /// or the voxel is already filled. Voxels the world has no room for are counted as missing.
+    pub fn set(&mut self, world: &mut World, pos: Vector3<f32>, color: u32) {
+        let Some(layer) = world.editable_layer() else {
+            return;
+        };
+
+        if world.contains(layer, pos) {
+            return;
+        }
+
+        if world.insert(layer, pos) {
+            // Voxel keeps the default color when there is no room for another painted one
+            world.set_color(layer, pos, color);
+            self.changes.push(Change { pos, layer, before: None, after: Some(world.color(layer, pos)) });
+        } else {
+            self.missing += 1;
+        }
+    }
+
+    /// Clears the voxel in the active layer, nothing happens if the layer is locked
+    pub fn clear(&mut self, world: &mut World, pos: Vector3<f32>) {
+        let Some(layer) = world.editable_layer() else {
+            return;
+        };
+
+        if world.contains(layer, pos) {
+            let before = Some(world.color(layer, pos));
+
+            world.remove(layer, pos);
+            self.changes.push(Change { pos, layer, before, after: None });
+        }
+    }
+
+    /// Recolors the voxel in the active layer, nothing happens if the layer is locked or the voxel is empty
+    pub fn paint(&mut self, world: &mut World, pos: Vector3<f32>, color: u32) {
+        let Some(layer) = world.editable_layer() else {
+            return;
+        };
+
+        let before = world.color(layer, pos);
+
+        if world.contains(layer, pos) && before != color && world.set_color(layer, pos, color) {
+            self.changes.push(Change { pos, layer, before: Some(before), after: Some(color) });
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Amount of voxels which couldn't be filled because the world was full
+    pub fn missing(&self) -> usize {
+        self.missing
+    }
+
+    // Returns the amount of voxels which didn't fit into the world
+    fn revert(&self, world: &mut World) -> usize {
+        let mut missing = 0;
+
+        for change in self.changes.iter().rev() {
+            if !change.apply(world, change.before) {
+                missing += 1;
+            }
+        }
+
+        missing
+    }
+
+    // Returns the amount of voxels which didn't fit into the world
+    fn apply(&self, world: &mut World) -> usize {
+        let mut missing = 0;
+
+        for change in &self.changes {
+            if !change.apply(world, change.after) {
+                missing += 1;
+            }
+        }
+
+        missing
+    }
+}
+
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl History {
+    /// Records an edit which was already applied to the world
+    pub fn push(&mut self, edit: Edit) {
+        if !edit.is_empty() {
+            self.undo.push(edit);
+            self.redo.clear();
+        }
+    }
+
+    /// Returns the amount of voxels which didn't fit into the world, None if there was nothing to undo
+    pub fn undo(&mut self, world: &mut World) -> Option<usize> {
+        let edit = self.undo.pop()?;
+        let missing = edit.revert(world);
+
+        self.redo.push(edit);
+        Some(missing)
+    }
+
+    /// Returns the amount of voxels which didn't fit into the world, None if there was nothing to redo
+    pub fn redo(&mut self, world: &mut World) -> Option<usize> {
+        let edit = self.redo.pop()?;
+        let missing = edit.apply(world);
+
+        self.undo.push(edit);
+        Some(missing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        octree::{
+            Octree,
+            MEMORY_SIZE
+        },
+        world::DEFAULT_COLOR,
+    };
+
+    fn set(world: &mut World, history: &mut History, pos: Vector3<f32>) {
+        let mut edit = Edit::default();
+        edit.set(world, pos, DEFAULT_COLOR);
+        history.push(edit);
+    }
+
+    #[test]
+    fn undo_and_redo_go_through_edits_in_reverse_order() {
+        let mut world = World::new(3);
+        let mut history = History::default();
+        let (a, b) = (Vector3::new(1.0, 2.0, 3.0), Vector3::new(4.0, 5.0, 6.0));
+
+        set(&mut world, &mut history, a);
+        set(&mut world, &mut history, b);
+
+        assert_eq!(history.undo(&mut world), Some(0));
+        assert!(world.contains(0, a) && !world.contains(0, b));
+        assert_eq!(history.undo(&mut world), Some(0));
+        assert!(!world.contains(0, a) && !world.contains(0, b));
+        assert_eq!(history.undo(&mut world), None);
+
+        assert_eq!(history.redo(&mut world), Some(0));
+        assert!(world.contains(0, a) && !world.contains(0, b));
+        assert_eq!(history.redo(&mut world), Some(0));
+        assert!(world.contains(0, a) && world.contains(0, b));
+        assert_eq!(history.redo(&mut world), None);
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut world = World::new(3);
+        let mut history = History::default();
+        let (a, b) = (Vector3::new(1.0, 2.0, 3.0), Vector3::new(4.0, 5.0, 6.0));
+
+        set(&mut world, &mut history, a);
+        history.undo(&mut world);
+        set(&mut world, &mut history, b);
+
+        assert_eq!(history.redo(&mut world), None);
+        assert!(!world.contains(0, a) && world.contains(0, b));
+    }
+
+    #[test]
+    fn empty_edit_keeps_redo() {
+        let mut world = World::new(3);
+        let mut history = History::default();
+        let a = Vector3::new(1.0, 2.0, 3.0);
+
+        set(&mut world, &mut history, a);
+        history.undo(&mut world);
+        history.push(Edit::default());
+
+        assert_eq!(history.redo(&mut world), Some(0));
+        assert!(world.contains(0, a));
+    }
+
+    #[test]
+    fn undo_restores_colors() {
+        let mut world = World::new(3);
+        let mut history = History::default();
+        let a = Vector3::new(1.0, 2.0, 3.0);
+
+        let mut edit = Edit::default();
+        edit.set(&mut world, a, 0xFF0000);
+        history.push(edit);
+
+        let mut edit = Edit::default();
+        edit.paint(&mut world, a, 0x00FF00);
+        history.push(edit);
+
+        let mut edit = Edit::default();
+        edit.clear(&mut world, a);
+        history.push(edit);
+
+        history.undo(&mut world);
+        assert_eq!(world.color(0, a), 0x00FF00);
+        history.undo(&mut world);
+        assert_eq!(world.color(0, a), 0xFF0000);
+        history.undo(&mut world);
+        assert!(!world.contains(0, a));
+        assert_eq!(world.color(0, a), DEFAULT_COLOR);
+    }
+    #[test]
+    fn redo_counts_voxels_which_no_longer_fit() {
+        let mut world = World::new(3);
+        let mut history = History::default();
+        let a = Vector3::new(1.0, 2.0, 3.0);
+
+        let mut edit = Edit::default();
+        edit.set(&mut world, a, 0xFF0000);
+        history.push(edit);
+        history.undo(&mut world);
+
+        // Chunk's octree without room for the blocks of another voxel
+        let octree = &mut world.chunks_mut()[0].octree;
+        *octree = Octree::new(3, Vec::new());
+        octree.free_address = (MEMORY_SIZE - 16) as u32;
+
+        assert_eq!(history.redo(&mut world), Some(1));
+        assert!(!world.contains(0, a));
+        assert_eq!(world.color(0, a), DEFAULT_COLOR);
+    }
+}
\ No newline at end of file
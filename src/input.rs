@@ -0,0 +1,502 @@
+use std::{
+    env,
+    fmt,
+    fs,
+    path::{
+        Path,
+        PathBuf
+    },
+};
+use winit::event::{
+    VirtualKeyCode,
+    MouseButton,
+    ModifiersState
+};
+use super::{
+    camera::PresetView,
+    mouse::Tool,
+};
+
+/// Bindings the editor starts with, in the format of the bindings file. Every line binds an action
+/// to inputs separated by commas, a line in the bindings file replaces the defaults of its action and
+/// one without inputs unbinds it. Inputs are key names like W, Space or F1 or the mouse buttons MouseLeft,
+/// MouseRight, MouseMiddle and Mouse4 and up, with any of Ctrl, Shift, Alt and Logo in front.
+pub const DEFAULT_BINDINGS: &str = "\
+move_forward = W
+move_backward = S
+move_left = A
+move_right = D
+move_up = E
+move_down = Q
+rotate_camera = Alt+MouseLeft
+pan_camera = MouseMiddle
+toggle_camera_mode = Tab
+toggle_projection = P
+view_front = Key1
+view_back = Key2
+view_left = Key3
+view_right = Key4
+view_top = Key5
+view_bottom = Key6
+view_isometric = Key7
+use_tool = MouseLeft
+erase = MouseRight
+tool_pencil = B
+tool_eraser = X
+tool_paint = G
+tool_eyedropper = I
+select = Shift+MouseLeft
+undo = Ctrl+Z
+redo = Ctrl+Shift+Z, Ctrl+Y
+copy = Ctrl+C
+paste = Ctrl+V
+export_selection = Ctrl+E
+deselect = Ctrl+D
+fill_selection = F
+clear_selection = Delete
+move_selection_left = Left
+move_selection_right = Right
+move_selection_backward = Down
+move_selection_forward = Up
+move_selection_down = PageDown
+move_selection_up = PageUp
+add_layer = N
+toggle_visible = H
+toggle_locked = L
+layer_1 = F1
+layer_2 = F2
+layer_3 = F3
+layer_4 = F4
+layer_5 = F5
+layer_6 = F6
+layer_7 = F7
+layer_8 = F8
+quit = Escape";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    RotateCamera,
+    PanCamera,
+    ToggleCameraMode,
+    ToggleProjection,
+    PresetView(PresetView),
+    UseTool,
+    Erase, // Whichever tool is active
+    SelectTool(Tool),
+    Select,
+    Undo,
+    Redo,
+    Copy,
+    Paste,
+    ExportSelection,
+    Deselect,
+    FillSelection,
+    ClearSelection,
+    MoveSelection(Direction),
+    AddLayer,
+    ToggleVisible,
+    ToggleLocked,
+    SelectLayer(usize),
+    Quit,
+}
+
+/// Direction along the axes of the world
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    Left,
+    Right,
+    Backward,
+    Forward,
+    Down,
+    Up,
+}
+
+const ACTIONS: &[(Action, &str)] = &[
+    (Action::MoveForward, "move_forward"),
+    (Action::MoveBackward, "move_backward"),
+    (Action::MoveLeft, "move_left"),
+    (Action::MoveRight, "move_right"),
+    (Action::MoveUp, "move_up"),
+    (Action::MoveDown, "move_down"),
+    (Action::RotateCamera, "rotate_camera"),
+    (Action::PanCamera, "pan_camera"),
+    (Action::ToggleCameraMode, "toggle_camera_mode"),
+    (Action::ToggleProjection, "toggle_projection"),
+    (Action::PresetView(PresetView::Front), "view_front"),
+    (Action::PresetView(PresetView::Back), "view_back"),
+    (Action::PresetView(PresetView::Left), "view_left"),
+    (Action::PresetView(PresetView::Right), "view_right"),
+    (Action::PresetView(PresetView::Top), "view_top"),
+    (Action::PresetView(PresetView::Bottom), "view_bottom"),
+    (Action::PresetView(PresetView::Isometric), "view_isometric"),
+    (Action::UseTool, "use_tool"),
+    (Action::Erase, "erase"),
+    (Action::SelectTool(Tool::Pencil), "tool_pencil"),
+    (Action::SelectTool(Tool::Eraser), "tool_eraser"),
+    (Action::SelectTool(Tool::Paint), "tool_paint"),
+    (Action::SelectTool(Tool::Eyedropper), "tool_eyedropper"),
+    (Action::Select, "select"),
+    (Action::Undo, "undo"),
+    (Action::Redo, "redo"),
+    (Action::Copy, "copy"),
+    (Action::Paste, "paste"),
+    (Action::ExportSelection, "export_selection"),
+    (Action::Deselect, "deselect"),
+    (Action::FillSelection, "fill_selection"),
+    (Action::ClearSelection, "clear_selection"),
+    (Action::MoveSelection(Direction::Left), "move_selection_left"),
+    (Action::MoveSelection(Direction::Right), "move_selection_right"),
+    (Action::MoveSelection(Direction::Backward), "move_selection_backward"),
+    (Action::MoveSelection(Direction::Forward), "move_selection_forward"),
+    (Action::MoveSelection(Direction::Down), "move_selection_down"),
+    (Action::MoveSelection(Direction::Up), "move_selection_up"),
+    (Action::AddLayer, "add_layer"),
+    (Action::ToggleVisible, "toggle_visible"),
+    (Action::ToggleLocked, "toggle_locked"),
+    (Action::SelectLayer(0), "layer_1"),
+    (Action::SelectLayer(1), "layer_2"),
+    (Action::SelectLayer(2), "layer_3"),
+    (Action::SelectLayer(3), "layer_4"),
+    (Action::SelectLayer(4), "layer_5"),
+    (Action::SelectLayer(5), "layer_6"),
+    (Action::SelectLayer(6), "layer_7"),
+    (Action::SelectLayer(7), "layer_8"),
+    (Action::Quit, "quit"),
+];
+
+// Keys bindings can use, named like their variants
+const KEYS: &[VirtualKeyCode] = &[
+    VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3, VirtualKeyCode::Key4, VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6, VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9, VirtualKeyCode::Key0,
+    VirtualKeyCode::A, VirtualKeyCode::B, VirtualKeyCode::C, VirtualKeyCode::D, VirtualKeyCode::E, VirtualKeyCode::F,
+    VirtualKeyCode::G, VirtualKeyCode::H, VirtualKeyCode::I, VirtualKeyCode::J, VirtualKeyCode::K, VirtualKeyCode::L,
+    VirtualKeyCode::M, VirtualKeyCode::N, VirtualKeyCode::O, VirtualKeyCode::P, VirtualKeyCode::Q, VirtualKeyCode::R,
+    VirtualKeyCode::S, VirtualKeyCode::T, VirtualKeyCode::U, VirtualKeyCode::V, VirtualKeyCode::W, VirtualKeyCode::X,
+    VirtualKeyCode::Y, VirtualKeyCode::Z,
+    VirtualKeyCode::F1, VirtualKeyCode::F2, VirtualKeyCode::F3, VirtualKeyCode::F4, VirtualKeyCode::F5, VirtualKeyCode::F6,
+    VirtualKeyCode::F7, VirtualKeyCode::F8, VirtualKeyCode::F9, VirtualKeyCode::F10, VirtualKeyCode::F11, VirtualKeyCode::F12,
+    VirtualKeyCode::Escape, VirtualKeyCode::Tab, VirtualKeyCode::Space, VirtualKeyCode::Return, VirtualKeyCode::Back,
+    VirtualKeyCode::Insert, VirtualKeyCode::Delete, VirtualKeyCode::Home, VirtualKeyCode::End,
+    VirtualKeyCode::PageUp, VirtualKeyCode::PageDown,
+    VirtualKeyCode::Left, VirtualKeyCode::Right, VirtualKeyCode::Up, VirtualKeyCode::Down,
+    VirtualKeyCode::Numpad0, VirtualKeyCode::Numpad1, VirtualKeyCode::Numpad2, VirtualKeyCode::Numpad3,
+    VirtualKeyCode::Numpad4, VirtualKeyCode::Numpad5, VirtualKeyCode::Numpad6, VirtualKeyCode::Numpad7,
+    VirtualKeyCode::Numpad8, VirtualKeyCode::Numpad9, VirtualKeyCode::NumpadAdd, VirtualKeyCode::NumpadSubtract,
+    VirtualKeyCode::NumpadMultiply, VirtualKeyCode::NumpadDivide, VirtualKeyCode::NumpadDecimal, VirtualKeyCode::NumpadEnter,
+    VirtualKeyCode::Apostrophe, VirtualKeyCode::Backslash, VirtualKeyCode::Comma, VirtualKeyCode::Equals,
+    VirtualKeyCode::LBracket, VirtualKeyCode::RBracket, VirtualKeyCode::Minus, VirtualKeyCode::Period,
+    VirtualKeyCode::Semicolon, VirtualKeyCode::Slash, VirtualKeyCode::Pause, VirtualKeyCode::Scroll,
+    VirtualKeyCode::Snapshot, VirtualKeyCode::Capital, VirtualKeyCode::Numlock, VirtualKeyCode::Compose,
+    VirtualKeyCode::Grave,
+];
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        ACTIONS.iter().find(|(action, _)| action == self).map(|(_, name)| *name).unwrap()
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        ACTIONS.iter().find(|(_, action_name)| *action_name == name).map(|(action, _)| *action)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Input {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+impl Input {
+    /// Key named like its variant or a mouse button named like the bindings file does
+    pub fn parse(name: &str) -> Result<Self, String> {
+        let mouse = match name.to_lowercase().as_str() {
+            "mouseleft" => Some(MouseButton::Left),
+            "mouseright" => Some(MouseButton::Right),
+            "mousemiddle" => Some(MouseButton::Middle),
+            other => other.strip_prefix("mouse").and_then(|number| number.parse().ok()).filter(|number| *number >= 4).map(MouseButton::Other),
+        };
+
+        if let Some(button) = mouse {
+            return Ok(Input::Mouse(button));
+        }
+
+        KEYS.iter()
+            .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+            .map(|key| Input::Key(*key))
+            .ok_or_else(|| format!("Unknown key or mouse button '{}'", name))
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Input::Key(key) => write!(f, "{:?}", key),
+            Input::Mouse(MouseButton::Left) => write!(f, "MouseLeft"),
+            Input::Mouse(MouseButton::Right) => write!(f, "MouseRight"),
+            Input::Mouse(MouseButton::Middle) => write!(f, "MouseMiddle"),
+            Input::Mouse(MouseButton::Other(number)) => write!(f, "Mouse{}", number),
+        }
+    }
+}
+
+/// Input together with the modifiers that have to be held for it
+#[derive(Clone, Copy, PartialEq)]
+struct Binding {
+    input: Input,
+    modifiers: ModifiersState,
+}
+
+impl Binding {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut parts = text.split('+').map(str::trim).collect::<Vec<_>>();
+        let input = Input::parse(parts.pop().unwrap())?;
+
+        // Opens the console, which takes every other key while it is open
+        if input == Input::Key(VirtualKeyCode::Grave) {
+            return Err(String::from("Grave is reserved for the console"));
+        }
+
+        let mut modifiers = ModifiersState::empty();
+
+        for part in parts {
+            modifiers |= match part.to_lowercase().as_str() {
+                "ctrl" => ModifiersState::CTRL,
+                "shift" => ModifiersState::SHIFT,
+                "alt" => ModifiersState::ALT,
+                "logo" => ModifiersState::LOGO,
+                _ => return Err(format!("Unknown modifier '{}' in '{}'", part, text)),
+            };
+        }
+
+        Ok(Self {
+            input,
+            modifiers,
+        })
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let modifiers = [
+            (self.modifiers.ctrl(), "Ctrl+"),
+            (self.modifiers.shift(), "Shift+"),
+            (self.modifiers.alt(), "Alt+"),
+            (self.modifiers.logo(), "Logo+"),
+        ];
+
+        for (_, name) in modifiers.iter().filter(|(held, _)| *held) {
+            write!(f, "{}", name)?;
+        }
+
+        write!(f, "{}", self.input)
+    }
+}
+
+/// Maps keys and mouse buttons to actions. Pressing an input starts the action bound to it with
+/// the held modifiers, or without modifiers when there is none, and releasing it stops that action
+/// whichever modifiers are held by then.
+pub struct InputMap {
+    bindings: Vec<(Binding, Action)>,
+    held: Vec<(Input, Action)>, // Actions started by inputs that are still down
+}
+
+impl InputMap {
+    /// Default bindings replaced by the ones in given bindings file, or in the file at the default
+    /// path when there is one
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let mut bindings = Vec::new();
+
+        parse(DEFAULT_BINDINGS, &mut bindings).expect("Invalid default bindings");
+
+        let path = path.map(Path::to_path_buf).or_else(|| default_path().filter(|path| path.exists()));
+
+        if let Some(path) = path {
+            let text = fs::read_to_string(&path).map_err(|error| format!("Couldn't read {}: {}", path.display(), error))?;
+
+            parse(&text, &mut bindings).map_err(|message| format!("{}: {}", path.display(), message))?;
+        }
+
+        for (index, (binding, action)) in bindings.iter().enumerate() {
+            if let Some((_, other)) = bindings[index + 1..].iter().find(|(other, _)| other == binding) {
+                return Err(format!("{} is bound to both '{}' and '{}'", binding, action.name(), other.name()));
+            }
+        }
+
+        Ok(Self {
+            bindings,
+            held: Vec::new(),
+        })
+    }
+
+    /// Action the pressed input starts
+    pub fn press(&mut self, input: Input, modifiers: ModifiersState) -> Option<Action> {
+        let action = self.find(input, modifiers).or_else(|| self.find(input, ModifiersState::empty()))?;
+
+        // Held keys repeat presses
+        if !self.held.contains(&(input, action)) {
+            self.held.push((input, action));
+        }
+
+        Some(action)
+    }
+
+    /// Actions the released input stops
+    pub fn release(&mut self, input: Input) -> Vec<Action> {
+        let (released, held) = self.held.iter().partition(|(held_input, _)| *held_input == input);
+
+        self.held = held;
+        released.into_iter().map(|(_, action)| action).collect()
+    }
+
+    fn find(&self, input: Input, modifiers: ModifiersState) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(binding, _)| binding.input == input && binding.modifiers == modifiers)
+            .map(|(_, action)| *action)
+    }
+}
+
+/// Bindings file in the config directory of the user
+pub fn default_path() -> Option<PathBuf> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .or_else(|| env::var_os("APPDATA"))
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(config.join("voxel_editor").join("bindings.txt"))
+}
+
+// Replaces the bindings of every action in the text
+fn parse(text: &str, bindings: &mut Vec<(Binding, Action)>) -> Result<(), String> {
+    for (number, line) in text.lines().enumerate().map(|(index, line)| (index + 1, line.trim())) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (name, inputs) = line.split_once('=').ok_or_else(|| format!("line {}: Expected 'action = inputs'", number))?;
+        let action = Action::parse(name.trim()).ok_or_else(|| format!("line {}: Unknown action '{}'", number, name.trim()))?;
+
+        bindings.retain(|(_, bound)| *bound != action);
+
+        for text in inputs.split(',').map(str::trim).filter(|text| !text.is_empty()) {
+            let binding = Binding::parse(text).map_err(|message| format!("line {}: {}", number, message))?;
+
+            bindings.push((binding, action));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Default bindings replaced by the ones in the text
+    fn map(text: &str) -> Result<InputMap, String> {
+        let mut bindings = Vec::new();
+
+        parse(DEFAULT_BINDINGS, &mut bindings)?;
+        parse(text, &mut bindings)?;
+
+        Ok(InputMap {
+            bindings,
+            held: Vec::new(),
+        })
+    }
+
+    fn key(key: VirtualKeyCode) -> Input {
+        Input::Key(key)
+    }
+
+    #[test]
+    fn default_bindings_bind_every_action() {
+        let mut bindings = Vec::new();
+
+        parse(DEFAULT_BINDINGS, &mut bindings).unwrap();
+
+        for (action, name) in ACTIONS {
+            assert!(bindings.iter().any(|(_, bound)| bound == action), "{} is unbound", name);
+        }
+    }
+
+    #[test]
+    fn parse_inputs_and_modifiers() {
+        assert!(Input::parse("space").unwrap() == key(VirtualKeyCode::Space));
+        assert!(Input::parse("F12").unwrap() == key(VirtualKeyCode::F12));
+        assert!(Input::parse("MouseMiddle").unwrap() == Input::Mouse(MouseButton::Middle));
+        assert!(Input::parse("Mouse5").unwrap() == Input::Mouse(MouseButton::Other(5)));
+
+        let binding = Binding::parse("ctrl + Shift+Z").unwrap();
+
+        assert!(binding.input == key(VirtualKeyCode::Z));
+        assert!(binding.modifiers == ModifiersState::CTRL | ModifiersState::SHIFT);
+        assert_eq!(binding.to_string(), "Ctrl+Shift+Z");
+        assert_eq!(Binding::parse("Alt+Logo+Mouse4").unwrap().to_string(), "Alt+Logo+Mouse4");
+    }
+
+    #[test]
+    fn parse_rejects_invalid_lines() {
+        assert_eq!(map("undo Ctrl+U").err().unwrap(), "line 1: Expected 'action = inputs'");
+        assert_eq!(map("\n# comment\nfly = Space").err().unwrap(), "line 3: Unknown action 'fly'");
+        assert_eq!(map("undo = Ctrl+Nope").err().unwrap(), "line 1: Unknown key or mouse button 'Nope'");
+        assert_eq!(map("undo = Hyper+U").err().unwrap(), "line 1: Unknown modifier 'Hyper' in 'Hyper+U'");
+        assert_eq!(map("quit = Grave").err().unwrap(), "line 1: Grave is reserved for the console");
+        assert!(Input::parse("Mouse3").is_err());
+    }
+
+    #[test]
+    fn lines_replace_the_defaults_of_their_action() {
+        let mut map = map("undo = Ctrl+U, Mouse4\nquit =").unwrap();
+
+        assert!(map.press(key(VirtualKeyCode::Z), ModifiersState::CTRL).is_none());
+        assert!(map.press(key(VirtualKeyCode::U), ModifiersState::CTRL) == Some(Action::Undo));
+        assert!(map.press(Input::Mouse(MouseButton::Other(4)), ModifiersState::empty()) == Some(Action::Undo));
+        assert!(map.press(key(VirtualKeyCode::Escape), ModifiersState::empty()).is_none());
+        assert!(map.press(key(VirtualKeyCode::Y), ModifiersState::CTRL) == Some(Action::Redo));
+    }
+
+    #[test]
+    fn press_falls_back_to_bindings_without_modifiers() {
+        let mut map = map("").unwrap();
+
+        assert!(map.press(Input::Mouse(MouseButton::Left), ModifiersState::SHIFT) == Some(Action::Select));
+        assert!(map.press(Input::Mouse(MouseButton::Left), ModifiersState::CTRL) == Some(Action::UseTool));
+        assert!(map.press(key(VirtualKeyCode::W), ModifiersState::SHIFT) == Some(Action::MoveForward));
+        assert!(map.press(key(VirtualKeyCode::K), ModifiersState::empty()).is_none());
+    }
+
+    #[test]
+    fn release_stops_actions_whichever_modifiers_are_held() {
+        let mut map = map("").unwrap();
+
+        map.press(key(VirtualKeyCode::W), ModifiersState::empty());
+        map.press(key(VirtualKeyCode::W), ModifiersState::empty());
+        map.press(Input::Mouse(MouseButton::Left), ModifiersState::ALT);
+        map.press(key(VirtualKeyCode::A), ModifiersState::empty());
+
+        assert!(map.release(key(VirtualKeyCode::W)) == vec![Action::MoveForward]);
+        assert!(map.release(key(VirtualKeyCode::W)).is_empty());
+        assert!(map.release(Input::Mouse(MouseButton::Left)) == vec![Action::RotateCamera]);
+        assert!(map.release(key(VirtualKeyCode::A)) == vec![Action::MoveLeft]);
+    }
+
+    #[test]
+    fn load_rejects_inputs_bound_twice() {
+        let path = env::temp_dir().join(format!("voxel_editor_bindings_{}.txt", std::process::id()));
+
+        fs::write(&path, "undo = Ctrl+Y").unwrap();
+
+        let result = InputMap::load(Some(&path));
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result.err().unwrap(), "Ctrl+Y is bound to both 'redo' and 'undo'");
+        assert!(InputMap::load(Some(Path::new("/nonexistent/bindings.txt"))).is_err());
+    }
+}
\ No newline at end of file
@@ -1,14 +1,124 @@
 mod octree;
+mod sdf;
+mod terrain;
+mod dag;
+mod scene;
+mod world;
+mod history;
+mod selection;
 mod camera;
 mod mouse;
 mod cursor;
+mod ray;
+mod raymarch;
 mod vulkan;
+mod format;
+mod vox;
+mod mesh;
+mod cli;
+mod console;
+mod headless;
+mod frames;
+mod input;
+mod editor;
+mod recording;
 
 use nalgebra::Vector3;
+use world::World;
 use octree::Octree;
+use camera::Camera;
+use scene::{
+    Instance,
+    quarter_turns
+};
 use vulkan::App;
+use input::InputMap;
+use editor::Editor;
+use recording::Recorder;
+use winit::dpi::PhysicalSize;
+use cli::{
+    Command,
+    EditorOptions,
+    ReplayOptions
+};
 
 fn main() {
+    let command = match cli::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, cli::USAGE);
+            std::process::exit(2);
+        },
+    };
+
+    match command {
+        Command::Help => println!("{}", cli::USAGE),
+        Command::Editor(options) => exit_on_error(editor(&options)),
+        Command::Render(options) => exit_on_error(headless::render(&options)),
+        Command::Convert { input, output } => exit_on_error(headless::convert(&input, &output)),
+        Command::Info(scene) => exit_on_error(headless::info(&scene)),
+        Command::Frames(options) => exit_on_error(headless::frames(&options)),
+        Command::Bindings => println!("{}", input::DEFAULT_BINDINGS),
+        Command::Replay(options) => exit_on_error(replay(&options)),
+        Command::Shape(options) => exit_on_error(headless::shape(&options)),
+        Command::Terrain(options) => exit_on_error(headless::terrain(&options)),
+    }
+}
+
+fn editor(options: &EditorOptions) -> Result<(), String> {
+    let editor = load_editor(options)?;
+    let recorder = options.record
+        .as_ref()
+        .map(|path| Recorder::create(path).map_err(|error| format!("Couldn't create {}: {}", path.display(), error)))
+        .transpose()?;
+    let replay = options.replay
+        .as_ref()
+        .map(|path| recording::load(path).map_err(|error| format!("Couldn't load {}: {}", path.display(), error)))
+        .transpose()?;
+
+    let app = App::new(editor, recorder, replay, options.validation);
+    app.prepare();
+    app.run();
+
+    Ok(())
+}
+
+fn replay(options: &ReplayOptions) -> Result<(), String> {
+    headless::replay(&mut load_editor(&options.editor)?, &options.recording, &options.output)
+}
+
+// Scene, camera and bindings the editor starts with
+fn load_editor(options: &EditorOptions) -> Result<Editor, String> {
+    let input_map = InputMap::load(options.bindings.as_deref())?;
+
+    let (world, (look_from, look_at)) = match (&options.scene, options.new_depth) {
+        (Some(path), _) => {
+            let world = format::load(path).map_err(|error| format!("Couldn't load {}: {}", path.display(), error))?;
+            let view = headless::default_view(&world);
+
+            (world, view)
+        },
+        (None, Some(depth)) => {
+            let world = World::new(depth);
+            let view = headless::default_view(&world);
+
+            (world, view)
+        },
+        // Demo scene keeps the view it was made for
+        (None, None) => (demo_world(), (Vector3::new(12.0, 0.0, 11.0), Vector3::new(12.0, 12.0, 9.0))),
+    };
+
+    let mut camera = Camera::new(
+        options.look_from.unwrap_or(look_from),
+        options.look_at.unwrap_or(look_at),
+    );
+    camera.set_field_of_view(options.field_of_view);
+    camera.set_aspect_ratio(options.width as f32 / options.height as f32);
+
+    Ok(Editor::new(world, camera, input_map, PhysicalSize::new(options.width, options.height)))
+}
+
+fn demo_world() -> World {
     let voxels = vec![
         Vector3::new(8.0, 8.0, 8.0),
         Vector3::new(15.0, 8.0, 9.0),
@@ -18,9 +128,32 @@ fn main() {
         Vector3::new(12.0, 11.0, 16.0)
     ];
 
-    let octree = Octree::new(Vector3::new(8, 8, 8), 16, voxels);
+    let mut world = World::new(3);
 
-    let app = App::new();
-    app.prepare(octree);
-    app.run();
+    for pos in voxels {
+        world.insert(0, pos);
+    }
+
+    // Same model placed a few times with different rotations
+    let model = Octree::new(2, vec![
+        Vector3::new(4.0, 4.0, 4.0),
+        Vector3::new(5.0, 4.0, 4.0),
+        Vector3::new(6.0, 4.0, 4.0),
+        Vector3::new(4.0, 5.0, 4.0),
+        Vector3::new(4.0, 4.0, 5.0),
+    ]);
+    let model = world.add_model(model).unwrap();
+
+    for (turns, x) in (0..4).zip((24..).step_by(6)) {
+        world.add_instance(Instance::new(model, Vector3::new(x, 8, 8), quarter_turns(2, turns)));
+    }
+
+    world
+}
+
+fn exit_on_error(result: Result<(), String>) {
+    if let Err(message) = result {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,94 @@
+use std::{
+    fs::File,
+    path::Path,
+    collections::{
+        HashMap,
+        HashSet
+    },
+    io::{
+        self,
+        Write,
+        BufWriter
+    },
+};
+use nalgebra::Vector3;
+
+/// Writes the surface of the voxels as a Wavefront OBJ mesh. Every face of a voxel which doesn't
+/// touch another voxel becomes a quad, corners shared by the quads are written only once.
+pub fn write_obj(path: &Path, voxels: &[Vector3<f32>]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+
+    let occupied = voxels.iter().map(|pos| pos.map(|x| x as i32)).collect::<HashSet<_>>();
+    let mut sorted = occupied.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|pos| (pos.z, pos.y, pos.x)); // Same voxels always give the same file
+
+    let mut vertices = HashMap::new();
+    let mut corners = Vec::new();
+    let mut faces = Vec::new();
+
+    for voxel in sorted {
+        for (normal_index, (axis, sign)) in (0..3).flat_map(|axis| [(axis, 1), (axis, -1)]).enumerate() {
+            let normal = Vector3::ith(axis, sign);
+
+            if occupied.contains(&(voxel + normal)) {
+                continue;
+            }
+
+            // Corners go counter-clockwise when looking at the face from outside
+            let (u, v) = (Vector3::ith((axis + 1) % 3, 1), Vector3::ith((axis + 2) % 3, 1));
+            let face = match sign {
+                1 => [voxel + normal, voxel + normal + u, voxel + normal + u + v, voxel + normal + v],
+                _ => [*voxel, voxel + v, voxel + u + v, voxel + u],
+            };
+
+            let indices = face.map(|corner| *vertices.entry(corner).or_insert_with(|| {
+                corners.push(corner);
+                corners.len() // OBJ indices start at 1
+            }));
+
+            faces.push((indices, normal_index + 1));
+        }
+    }
+
+    writeln!(file, "# {} voxels, {} faces", occupied.len(), faces.len())?;
+
+    for corner in corners {
+        writeln!(file, "v {} {} {}", corner.x, corner.y, corner.z)?;
+    }
+
+    for axis in 0..3 {
+        for sign in [1, -1] {
+            let normal = Vector3::<i32>::ith(axis, sign);
+            writeln!(file, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+        }
+    }
+
+    for ([a, b, c, d], normal) in faces {
+        writeln!(file, "f {a}//{normal} {b}//{normal} {c}//{normal} {d}//{normal}")?;
+    }
+
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adjacent_voxels_share_their_touching_faces_and_corners() {
+        let path = std::env::temp_dir().join(format!("voxel_editor_mesh_{}.obj", std::process::id()));
+
+        write_obj(&path, &[Vector3::new(1.0, 2.0, 3.0), Vector3::new(2.0, 2.0, 3.0)]).unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let count = |prefix: &str| text.lines().filter(|line| line.starts_with(prefix)).count();
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(text.lines().next(), Some("# 2 voxels, 10 faces"));
+        assert_eq!(count("v "), 12);
+        assert_eq!(count("vn "), 6);
+        assert_eq!(count("f "), 10);
+        assert!(text.contains("v 3 3 4"));
+    }
+}
\ No newline at end of file
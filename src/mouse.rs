@@ -1,20 +1,48 @@
 use ash::vk;
 use nalgebra::Vector2;
 use winit::{
-    event::{
-        MouseButton, 
-        ElementState
-    }, 
-    dpi::PhysicalPosition, 
-    window::Window
+    event::ElementState, 
+    dpi::{
+        PhysicalPosition,
+        PhysicalSize
+    }
 };
+use super::input::Action;
+
+/// What the left mouse button does, numbered like the TOOL_ defines in mouse.glsl
+#[derive(Clone, Copy, PartialEq)]
+pub enum Tool {
+    Pencil, // Adds voxels in the active color
+    Eraser,
+    Paint, // Recolors voxels
+    Eyedropper, // Makes the color of a voxel the active one
+}
 
+impl Tool {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tool::Pencil => "pencil",
+            Tool::Eraser => "eraser",
+            Tool::Paint => "paint",
+            Tool::Eyedropper => "eyedropper",
+        }
+    }
+}
 
 #[repr(C)]
+#[derive(Default)]
 pub struct MouseState {
     coordinate: Vector2<f32>,
     pub left_button: vk::Bool32,
-    right_button: vk::Bool32,
+    pub right_button: vk::Bool32,
+    pub tool: u32,
+    pub color: u32, // 0xRRGGBB
+}
+
+impl MouseState {
+    pub fn coordinate(&self) -> Vector2<f32> {
+        self.coordinate
+    }
 }
 
 #[repr(C)]
@@ -25,34 +53,41 @@ pub struct Mouse {
 }
 
 impl Mouse {
-    pub fn process_input(&mut self, button: MouseButton, state: ElementState) {
+    /// Tool and erase actions edit at the cursor
+    pub fn process_action(&mut self, action: Action, state: ElementState) {
         let pressed = match state {
             ElementState::Pressed => vk::TRUE,
             ElementState::Released => vk::FALSE,
         };
 
-        match button {
-            MouseButton::Left => self.left_button = pressed,
-            MouseButton::Right => self.right_button = pressed,
+        match action {
+            Action::UseTool => self.left_button = pressed,
+            Action::Erase => self.right_button = pressed,
             _ => (),
         };
     }
 
-    pub fn process_movement(&mut self, position: PhysicalPosition<f64>, window: &Window) {
+    /// Position in pixels of a window of given size
+    pub fn process_movement(&mut self, position: PhysicalPosition<f64>, size: PhysicalSize<u32>) {
         self.coordinate = Vector2::new(
-            position.cast::<f32>().x / window.inner_size().width as f32, 
-            position.cast::<f32>().y / window.inner_size().height as f32,
+            position.cast::<f32>().x / size.width as f32, 
+            position.cast::<f32>().y / size.height as f32,
         );
     }
 
-    pub fn state(&mut self) -> MouseState {
+    /// State the shaders edit with, clicks are only used by a single frame
+    pub fn state(&mut self, tool: Tool, color: u32) -> MouseState {
         let left_button = self.left_button;
+        let right_button = self.right_button;
         self.left_button = vk::FALSE;
+        self.right_button = vk::FALSE;
 
         MouseState { 
             coordinate: self.coordinate, 
             left_button,
-            right_button: self.right_button, 
+            right_button, 
+            tool: tool as u32,
+            color,
         }
     }
 }   
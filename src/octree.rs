@@ -4,9 +4,12 @@ use nalgebra::{
 };
 
 pub const MEMORY_SIZE: usize = 1024; 
-const STACK_SIZE: usize = 23;
+pub const STACK_SIZE: usize = 23;
+
+// Descriptor holds the valid mask in its low 8 bits and the child pointer in the upper 24 bits
 
 #[repr(C)]
+#[derive(Clone)]
 pub struct Bounds {
     min: Vector4<f32>, // Even though min and max are 3 dimensional vectors they need to be Vector4
     max: Vector4<f32>, // becuase vec3 are 4 bytes aligned in GLSL
@@ -22,83 +25,375 @@ impl Bounds {
             max: max.to_homogeneous(), 
         }
     }
+
+    pub fn min(&self) -> Vector3<f32> {
+        self.min.xyz()
+    }
+
+    pub fn max(&self) -> Vector3<f32> {
+        self.max.xyz()
+    }
+
+    pub fn contains(&self, pos: Vector3<f32>) -> bool {
+        (0..3).all(|axis| pos[axis] >= self.min[axis] && pos[axis] < self.max[axis])
+    }
 }
 
-#[repr(C)]
+// Aligned like GLSL aligns structs holding a vec3, so arrays of octrees have the same stride
+#[repr(C, align(16))]
+#[derive(Clone)]
 pub struct Octree {
     pub bounds: Bounds,
     pub descriptors: [u32; MEMORY_SIZE],
     pub free_address: u32,
+    pub depth: u32,
 }
 
 impl Octree {
-    pub fn new(location: Vector3<i32>, size: u32, voxels: Vec<Vector3<f32>>) -> Self {
-        let (descriptors, free_address)  = {
-            let mut descriptors = [u32::default(); MEMORY_SIZE];
-            let mut stack = [u32::default(); (STACK_SIZE + 1) as usize];
-            let mut free_address = 0;
-        
-            let lowest_scale = 20 + 1; // Lowest non-leaf voxel scale
-
-            for mut pos in voxels {
-                // Find cube position at current scale
-                for scale in lowest_scale..=STACK_SIZE {
-                    let shx = pos.x.to_bits() >> scale;
-                    let shy = pos.y.to_bits() >> scale;
-                    let shz = pos.z.to_bits() >> scale;
-                    let prime_x = f32::from_bits(shx << scale);
-                    let prime_y = f32::from_bits(shy << scale);
-                    let prime_z = f32::from_bits(shz << scale);
-        
-                    let mut idx: u32 = 0;
-                
-                    if pos.x > prime_x {
-                        idx |= 1;
-                    }
-                    if pos.y > prime_y {
-                        idx |= 1 << 1;
-                    }
-                    if pos.z > prime_z {
-                        idx |= 1 << 2;
-                    }
-        
-                    pos.x = prime_x;
-                    pos.y = prime_y;
-                    pos.z = prime_z;
-        
-                    stack[scale as usize] = 1 << idx;
-                }
-
-                // Put them into octree
-                let mut parent_valid_mask = stack[STACK_SIZE];
-                let mut parent_child_pointer = 0;
-
-                descriptors[MEMORY_SIZE - 1] |= parent_valid_mask;
-
-                for scale in (lowest_scale..STACK_SIZE).rev() {
-                    let child_address = parent_child_pointer + (31 - parent_valid_mask.leading_zeros());
-                    let mut current_descriptor = descriptors[child_address as usize];
-                    let valid_mask = stack[scale as usize];
-                    
-                    current_descriptor |= valid_mask;
-                    
-                    if current_descriptor >> 8 == 0 && scale != lowest_scale {
-                        free_address += 8;
-                        current_descriptor |= free_address << 8;
-                    }
-                    
-                    parent_valid_mask = valid_mask;
-                    parent_child_pointer = current_descriptor >> 8;
-                    descriptors[child_address as usize] = current_descriptor;
-                }
+    /// Octree of the given depth resides at coordinates [2^depth, 2^(depth + 1)]
+    /// which keeps every leaf voxel exactly one unit in size
+    pub fn new(depth: u32, voxels: Vec<Vector3<f32>>) -> Self {
+        assert!(depth >= 1 && depth < STACK_SIZE as u32, "Octree depth must be in range [1, {})", STACK_SIZE);
+
+        let mut octree = Self { 
+            bounds: Bounds::new(Vector3::repeat(1 << depth), 1 << depth),
+            descriptors: [u32::default(); MEMORY_SIZE],
+            free_address: 0,
+            depth,
+        };
+
+        for pos in voxels {
+            let inserted = octree.insert(pos);
+            assert!(inserted, "Voxels don't fit into octree memory");
+        }
+
+        octree
+    }
+
+    /// Fills a voxel, returns false and leaves the octree unchanged if there is no memory for it
+    pub fn insert(&mut self, pos: Vector3<f32>) -> bool {
+        self.fill(pos, 1)
+    }
+
+    /// Fills the cube of voxels with its lower corner at pos, its size has to be a power of two and
+    /// pos a multiple of it. Returns false and leaves the octree unchanged if there is no memory for it.
+    pub fn fill(&mut self, pos: Vector3<f32>, size: u32) -> bool {
+        assert!(size.is_power_of_two() && size <= self.size(), "Filled cube must be a power of two within the octree");
+
+        let stack = self.valid_masks(pos);
+        let lowest_scale = self.leaf_scale() + 1; // Lowest non-leaf voxel scale
+        let cube_scale = self.leaf_scale() + size.trailing_zeros() as usize;
+
+        if !self.has_room(self.missing_blocks(&stack, cube_scale)) {
+            return false;
+        }
+
+        // Set the valid masks of the cube's ancestors, starting at the root
+        let mut address = MEMORY_SIZE - 1;
+
+        for scale in (cube_scale + 1..=STACK_SIZE).rev() {
+            self.descriptors[address] |= stack[scale];
+
+            if scale > lowest_scale {
+                address = (self.child_block(address) + stack[scale].trailing_zeros()) as usize;
             }
-            (descriptors, free_address)
+        }
+
+        // Single voxels are leaves, which have no descriptor of their own
+        if cube_scale > self.leaf_scale() {
+            self.fill_node(address, cube_scale);
+        }
+
+        true
+    }
+
+    /// Clears a voxel, ancestors left without children are cleared as well
+    pub fn remove(&mut self, pos: Vector3<f32>) {
+        if !self.bounds.contains(pos) {
+            return;
+        }
+
+        let stack = self.valid_masks(pos);
+
+        let Some(addresses) = self.find(&stack) else {
+            return;
         };
 
-        Self { 
-            bounds: Bounds::new(location, size),
-            descriptors,
-            free_address,
+        // Child blocks stay allocated, so inserting the voxel again reuses them
+        for scale in self.leaf_scale() + 1..=STACK_SIZE {
+            let descriptor = &mut self.descriptors[addresses[scale]];
+            *descriptor &= !stack[scale];
+
+            if *descriptor & 0xFF != 0 {
+                break;
+            }
         }
     }
+
+    pub fn contains(&self, pos: Vector3<f32>) -> bool {
+        self.bounds.contains(pos) && self.find(&self.valid_masks(pos)).is_some()
+    }
+
+    /// Reserves 8 descriptors for the children of a descriptor and returns their address
+    pub fn allocate_block(&mut self) -> u32 {
+        self.free_address += 8;
+        self.check_memory();
+        self.free_address
+    }
+
+    /// Amount of leaf voxels along each axis
+    pub fn size(&self) -> u32 {
+        1 << self.depth
+    }
+
+    /// Lower corners of the set leaf voxels in the octree's coordinates
+    pub fn voxels(&self) -> Vec<Vector3<f32>> {
+        let mut voxels = Vec::new();
+
+        self.walk(self.descriptors[MEMORY_SIZE - 1], 0, self.bounds.min(), &mut |level, pos| {
+            if level == self.depth {
+                voxels.push(pos);
+            }
+        });
+
+        voxels
+    }
+
+    /// Amount of nodes in the tree including the root and the leaf voxels
+    pub fn node_count(&self) -> usize {
+        let mut count = 1;
+
+        self.walk(self.descriptors[MEMORY_SIZE - 1], 0, self.bounds.min(), &mut |_, _| count += 1);
+
+        count
+    }
+
+    // Valid mask of the voxel and each of its ancestors within their parents, indexed by scale
+    fn valid_masks(&self, mut pos: Vector3<f32>) -> [u32; STACK_SIZE + 1] {
+        let mut stack = [u32::default(); STACK_SIZE + 1];
+        let lowest_scale = self.leaf_scale() + 1; // Lowest non-leaf voxel scale
+
+        // Find cube position at current scale
+        for (scale, mask) in stack.iter_mut().enumerate().skip(lowest_scale) {
+            let shx = pos.x.to_bits() >> scale;
+            let shy = pos.y.to_bits() >> scale;
+            let shz = pos.z.to_bits() >> scale;
+            let prime_x = f32::from_bits(shx << scale);
+            let prime_y = f32::from_bits(shy << scale);
+            let prime_z = f32::from_bits(shz << scale);
+
+            let mut idx: u32 = 0;
+
+            if pos.x > prime_x {
+                idx |= 1;
+            }
+            if pos.y > prime_y {
+                idx |= 1 << 1;
+            }
+            if pos.z > prime_z {
+                idx |= 1 << 2;
+            }
+
+            pos.x = prime_x;
+            pos.y = prime_y;
+            pos.z = prime_z;
+
+            *mask = 1 << idx;
+        }
+
+        stack
+    }
+
+    // Addresses of the descriptors holding the voxel and its ancestors, if the voxel is set
+    fn find(&self, stack: &[u32; STACK_SIZE + 1]) -> Option<[usize; STACK_SIZE + 1]> {
+        let mut addresses = [MEMORY_SIZE - 1; STACK_SIZE + 1];
+
+        for scale in (self.leaf_scale() + 1..=STACK_SIZE).rev() {
+            let descriptor = self.descriptors[addresses[scale]];
+
+            if descriptor & stack[scale] == 0 {
+                return None;
+            }
+
+            if scale > self.leaf_scale() + 1 {
+                addresses[scale - 1] = ((descriptor >> 8) + stack[scale].trailing_zeros()) as usize;
+            }
+        }
+
+        Some(addresses)
+    }
+
+    // Calls visit with the level and lower corner of every child of the node at given level (root is level 0)
+    fn walk(&self, descriptor: u32, level: u32, lower: Vector3<f32>, visit: &mut impl FnMut(u32, Vector3<f32>)) {
+        let child_size = (1 << (self.depth - level - 1)) as f32;
+
+        for idx in (0..8).filter(|idx| descriptor & 1 << idx != 0) {
+            let offset = Vector3::new(idx & 1, idx >> 1 & 1, idx >> 2 & 1).cast::<f32>();
+            let child_lower = lower + offset * child_size;
+
+            visit(level + 1, child_lower);
+
+            // Lowest descriptors hold the valid masks of leaf voxels, which have no children
+            if level + 1 < self.depth {
+                let child = self.descriptors[((descriptor >> 8) + idx) as usize];
+                self.walk(child, level + 1, child_lower, visit);
+            }
+        }
+    }
+
+    /// Scale of the leaf voxels in the same terms as the shaders use
+    fn leaf_scale(&self) -> usize {
+        STACK_SIZE - self.depth as usize
+    }
+
+    // Every descriptor of the node at given scale and its descendants gets a full valid mask
+    fn fill_node(&mut self, address: usize, scale: usize) {
+        self.descriptors[address] |= 0xFF;
+
+        if scale > self.leaf_scale() + 1 {
+            let block = self.child_block(address);
+
+            for idx in 0..8 {
+                self.fill_node((block + idx) as usize, scale - 1);
+            }
+        }
+    }
+
+    // Address of the descriptor's children, which are allocated if it has none yet
+    fn child_block(&mut self, address: usize) -> u32 {
+        // Children of the root start at 0
+        if address == MEMORY_SIZE - 1 {
+            return 0;
+        }
+
+        if self.descriptors[address] >> 8 == 0 {
+            let block = self.allocate_block();
+            self.descriptors[address] |= block << 8;
+        }
+
+        self.descriptors[address] >> 8
+    }
+
+    // Amount of child blocks filling the cube at given scale allocates. Once a descriptor on the way
+    // down has no children, every level below it needs a new block.
+    fn missing_blocks(&self, stack: &[u32; STACK_SIZE + 1], cube_scale: usize) -> usize {
+        let lowest_scale = self.leaf_scale() + 1;
+        let mut address = stack[STACK_SIZE].trailing_zeros(); // Children of the root start at 0
+
+        for scale in (cube_scale.max(lowest_scale) + 1..STACK_SIZE).rev() {
+            let descriptor = self.descriptors[address as usize];
+
+            if descriptor >> 8 == 0 {
+                return scale - cube_scale.max(lowest_scale) + self.full_blocks(cube_scale);
+            }
+
+            address = (descriptor >> 8) + stack[scale].trailing_zeros();
+        }
+
+        match cube_scale {
+            scale if scale == STACK_SIZE => self.subtree_blocks(MEMORY_SIZE - 1, scale),
+            scale if scale > self.leaf_scale() => self.subtree_blocks(address as usize, scale),
+            _ => 0,
+        }
+    }
+
+    // Amount of child blocks under the descriptor at given scale which don't exist yet
+    fn subtree_blocks(&self, address: usize, scale: usize) -> usize {
+        if scale <= self.leaf_scale() + 1 {
+            return 0;
+        }
+
+        match self.descriptors[address] >> 8 {
+            0 if address != MEMORY_SIZE - 1 => self.full_blocks(scale),
+            block => (block..block + 8).map(|child| self.subtree_blocks(child as usize, scale - 1)).sum(),
+        }
+    }
+
+    // Amount of child blocks under a new full descriptor at given scale
+    fn full_blocks(&self, scale: usize) -> usize {
+        match scale > self.leaf_scale() + 1 {
+            true => 1 + 8 * self.full_blocks(scale - 1),
+            false => 0,
+        }
+    }
+
+    // Same bound as check_memory() for the last of the blocks
+    fn has_room(&self, blocks: usize) -> bool {
+        self.free_address as usize + 8 * (blocks + 1) < MEMORY_SIZE - 1
+    }
+
+    // Allocated blocks grow up from the start towards the root at the end
+    fn check_memory(&self) {
+        assert!(self.free_address as usize + 8 < MEMORY_SIZE - 1, "Octree memory is exhausted");
+    }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every leaf voxel of an octree of the given size
+    fn cube(size: u32) -> impl Iterator<Item = Vector3<f32>> {
+        (size..2 * size).flat_map(move |z| {
+            (size..2 * size).flat_map(move |y| (size..2 * size).map(move |x| Vector3::new(x, y, z).cast::<f32>()))
+        })
+    }
+
+    #[test]
+    fn full_depth_4_octree_fits() {
+        let mut octree = Octree::new(4, Vec::new());
+
+        assert!(cube(16).all(|pos| octree.insert(pos)));
+        assert_eq!(octree.voxels().len(), 16 * 16 * 16);
+    }
+
+    #[test]
+    fn insert_into_full_octree_leaves_it_unchanged() {
+        let mut octree = Octree::new(5, Vec::new());
+        let (inserted, missing): (Vec<_>, Vec<_>) = cube(32).partition(|pos| octree.insert(*pos));
+
+        assert!(!missing.is_empty());
+        assert!(inserted.iter().all(|pos| octree.contains(*pos)));
+        assert!(missing.iter().all(|pos| !octree.contains(*pos)));
+        assert_eq!(octree.voxels().len(), inserted.len());
+
+        // Filled voxels need no new blocks
+        assert!(octree.insert(inserted[0]));
+    }
+
+    #[test]
+    fn fill_matches_inserting_every_voxel() {
+        let mut filled = Octree::new(4, Vec::new());
+        let mut inserted = Octree::new(4, Vec::new());
+        let lower = Vector3::new(24.0, 16.0, 20.0);
+
+        // Overlapping a voxel that is already there reuses its blocks
+        assert!(filled.insert(Vector3::new(25.0, 17.0, 21.0)));
+        assert!(filled.fill(lower, 4));
+        assert!(filled.fill(Vector3::new(16.0, 16.0, 16.0), 2));
+        assert!(filled.fill(Vector3::new(30.0, 30.0, 30.0), 1));
+
+        for pos in cube(4).map(|pos| pos - Vector3::repeat(4.0) + lower)
+            .chain(cube(2).map(|pos| pos + Vector3::repeat(14.0)))
+            .chain([Vector3::new(30.0, 30.0, 30.0)]) {
+            assert!(inserted.insert(pos));
+        }
+
+        let mut filled_voxels = filled.voxels();
+        let mut inserted_voxels = inserted.voxels();
+        filled_voxels.sort_by(|a, b| a.as_slice().partial_cmp(b.as_slice()).unwrap());
+        inserted_voxels.sort_by(|a, b| a.as_slice().partial_cmp(b.as_slice()).unwrap());
+
+        assert_eq!(filled_voxels, inserted_voxels);
+        assert_eq!(filled.free_address, inserted.free_address);
+    }
+
+    #[test]
+    fn fill_whole_octree() {
+        let mut octree = Octree::new(3, Vec::new());
+
+        assert!(octree.fill(octree.bounds.min(), 8));
+        assert_eq!(octree.voxels().len(), 8 * 8 * 8);
+        assert!(!Octree::new(5, Vec::new()).fill(Vector3::repeat(32.0), 32));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,29 @@
+use nalgebra::{
+    Vector3,
+    Vector4
+};
+use super::camera::CameraProjection;
+
+pub struct Ray {
+    pub origin: Vector3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    // Same as create_ray() in ray.glsl
+    pub fn new(camera: &CameraProjection, u: f32, v: f32) -> Self {
+        let view_plane_pos = camera.upper_left_corner.xyz() + u * camera.horizontal.xyz() - v * camera.vertical.xyz();
+
+        if camera.direction != Vector4::zeros() {
+            return Self {
+                origin: view_plane_pos,
+                direction: camera.direction.xyz(),
+            };
+        }
+
+        Self {
+            origin: camera.origin.xyz(),
+            direction: view_plane_pos - camera.origin.xyz(),
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,859 @@
+use ash::vk;
+use nalgebra::{
+    Vector2,
+    Vector3,
+    Vector4
+};
+use super::{
+    octree::{
+        Octree,
+        MEMORY_SIZE,
+        STACK_SIZE
+    },
+    world::{
+        World,
+        DEFAULT_COLOR
+    },
+    scene::BvhNode,
+    camera::CameraProjection,
+    cursor::Cursor,
+    mouse::{
+        MouseState,
+        Tool
+    },
+    selection::Selection,
+    ray::Ray,
+};
+
+// CPU port of the world traversal in raymarch.glsl, render.comp, raycast_mouse.comp and edit.comp. It has to
+// stay in sync with the shaders operation by operation, so the GLSL min(), max(), findMSB()
+// and bit casts are spelled out instead of using their slightly different Rust counterparts.
+
+const EPS: f32 = 1.1920929e-7; // 2^(-23)
+const BVH_STACK_SIZE: usize = 32;
+
+#[derive(Clone, Copy, Default)]
+struct StackItem {
+    node: u32,
+    t_max: f32,
+}
+
+/// Leaf voxel hit by a ray, normal is only set along the axis of the hit side
+pub struct Hit {
+    pub pos: Vector3<f32>,
+    pub normal: Vector3<i32>,
+    pub t: f32,
+    pub local_pos: Vector3<f32>, // Voxel of the instance's model, counted from the model's lower corner
+    pub instance: Option<usize>, // None for voxels of the chunks
+    pub layer: usize, // Layer of the chunk's voxel
+}
+
+/// Ray and hit position are in the coordinates of the octree, t is the ray parameter at the hit
+pub fn octree_raymarch(octree: &Octree, o: Vector3<f32>, mut d: Vector3<f32>) -> Option<Hit> {
+    // Octree resides at coordinates [2^depth, 2^(depth + 1)] ([1, 2] * 2^depth)
+    let lower_bound = octree.bounds.min().x;
+    let upper_bound = lower_bound * 2.0;
+    let mid_point = (lower_bound + upper_bound) * 0.5;
+    let mirror = lower_bound + upper_bound;
+
+    // Get rid of small ray direction components to avoid division by zero
+    for i in 0..3 {
+        d[i] = if d[i].abs() > EPS { d[i] } else if d[i] >= 0.0 { EPS } else { -EPS };
+    }
+
+    // Precompute the coefficients of tx(x), ty(y), and tz(z)
+    let t_coef = d.map(|d| 1.0 / -d.abs());
+    let mut t_bias = t_coef.component_mul(&o);
+
+    // Select octant mask to mirror the coordinate system so
+    // that ray direction is negative along each axis.
+    let mut oct_mask = 0;
+    let mut d_rev = Vector3::<i32>::repeat(1); // Reversed ray direction, needed later for finding the hit side
+
+    for i in 0..3 {
+        if d[i] > 0.0 {
+            oct_mask ^= 1 << i;
+            d_rev[i] = -1;
+            t_bias[i] = mirror * t_coef[i] - t_bias[i];
+        }
+    }
+
+    // Initialize the active span of t-values.
+    let mut t_min = max(max(upper_bound * t_coef.x - t_bias.x, upper_bound * t_coef.y - t_bias.y), upper_bound * t_coef.z - t_bias.z);
+    let mut t_max = min(min(lower_bound * t_coef.x - t_bias.x, lower_bound * t_coef.y - t_bias.y), lower_bound * t_coef.z - t_bias.z);
+    t_min = max(t_min, 0.0);
+    let mut h = t_max;
+
+    // If the ray missed the root
+    if t_min > t_max {
+        return None;
+    }
+
+    // Root is located at the last location in WorldBuffer
+    let mut parent = octree.descriptors[MEMORY_SIZE - 1];
+    // Cached child descriptor
+    let mut descriptor = 0;
+    let mut pos = Vector3::repeat(lower_bound);
+    let mut idx: u32 = 0;
+
+    for i in 0..3 {
+        if mid_point * t_coef[i] - t_bias[i] > t_min {
+            idx ^= 1 << i;
+            pos[i] = mid_point;
+        }
+    }
+
+    let mut scale = STACK_SIZE as u32 - 1;
+    let leaf_scale = STACK_SIZE as u32 - octree.depth;
+    let mut scale_exp2 = 0.5 * lower_bound;
+    let mut stack = [StackItem::default(); STACK_SIZE];
+
+    while scale < STACK_SIZE as u32 {
+        if descriptor == 0 {
+            descriptor = octree.descriptors[((parent >> 8) + (idx ^ oct_mask)) as usize];
+        }
+
+        // Determine maximum t-value of the cube by evaluating tx(), ty(), and tz() at its corner
+        let t_corner = pos.component_mul(&t_coef) - t_bias;
+        let tc_max = min(min(t_corner.x, t_corner.y), t_corner.z);
+
+        if (parent & 1 << (idx ^ oct_mask)) != 0 && t_min <= t_max {
+            // INTERSECT
+            let tv_max = min(t_max, tc_max);
+            let half_scale_exp2 = scale_exp2 * 0.5;
+            // Intersect active t-span with the cube and evaluate tx(), ty(), and tz() at the center of the voxel
+            let t_center = half_scale_exp2 * t_coef + t_corner;
+
+            if t_min <= tv_max {
+                // If ray has hit a leaf child
+                if scale == leaf_scale {
+                    // Undo the mirroring of the coordinate system
+                    for i in 0..3 {
+                        if (oct_mask & 1 << i) != 0 {
+                            pos[i] = ((upper_bound - 1.0) - pos[i]) + lower_bound;
+                        }
+                    }
+
+                    // Determine which side is hit
+                    let t_opp_corner = scale_exp2 * t_coef + t_corner;
+
+                    let axis = if t_opp_corner.x > t_opp_corner.y && t_opp_corner.x > t_opp_corner.z {
+                        0
+                    } else if t_opp_corner.y > t_opp_corner.z {
+                        1
+                    } else {
+                        2
+                    };
+
+                    let mut normal = Vector3::zeros();
+                    normal[axis] = d_rev[axis];
+
+                    return Some(Hit {
+                        pos,
+                        normal,
+                        t: t_min,
+                        local_pos: pos,
+                        instance: None,
+                        layer: 0,
+                    });
+                }
+
+                // PUSH
+                if tc_max < h {
+                    stack[scale as usize] = StackItem {
+                        node: parent,
+                        t_max,
+                    };
+                }
+
+                h = tc_max;
+                parent = descriptor;
+
+                // Select child voxel that the ray enters first
+                idx = 0;
+                scale -= 1;
+                scale_exp2 = half_scale_exp2;
+
+                for i in 0..3 {
+                    if t_center[i] > t_min {
+                        idx ^= 1 << i;
+                        pos[i] += scale_exp2;
+                    }
+                }
+
+                descriptor = 0;
+                t_max = tv_max;
+
+                continue;
+            }
+        }
+
+        // ADVANCE
+        let mut step_mask = 0;
+
+        for i in 0..3 {
+            if t_corner[i] <= tc_max {
+                step_mask ^= 1 << i;
+                pos[i] -= scale_exp2;
+            }
+        }
+
+        // Update active t-span and flip bits of the child slot index
+        t_min = tc_max;
+        idx ^= step_mask;
+
+        // If the ray exists the octree without hitting a leaf voxel
+        if pos.x < lower_bound || pos.y < lower_bound || pos.z < lower_bound {
+            return None;
+        }
+
+        // Proceed with pop if the bit flips disagree with the ray direction
+        if (idx & step_mask) != 0 {
+            // POP
+            // Find the highest differing bit between two positions
+            let mut differing_bits = 0;
+
+            for i in 0..3 {
+                if (step_mask & 1 << i) != 0 {
+                    differing_bits |= pos[i].to_bits() ^ (pos[i] + scale_exp2).to_bits();
+                }
+            }
+
+            // Find MSB
+            scale = find_msb(differing_bits);
+
+            // The shaders read past the stack here and then leave the loop
+            if scale >= STACK_SIZE as u32 {
+                break;
+            }
+
+            scale_exp2 = f32::from_bits((scale + 127 - STACK_SIZE as u32) << 23) * lower_bound; // exp2f(scale - s_max) * lower_bound
+
+            // Restore parent voxel from the stack
+            parent = stack[scale as usize].node;
+            t_max = stack[scale as usize].t_max;
+
+            // Round cube position and extract child slot index
+            let shx = pos.x.to_bits() >> scale;
+            let shy = pos.y.to_bits() >> scale;
+            let shz = pos.z.to_bits() >> scale;
+            pos.x = f32::from_bits(shx << scale);
+            pos.y = f32::from_bits(shy << scale);
+            pos.z = f32::from_bits(shz << scale);
+
+            idx = (shx & 1) | ((shy & 1) << 1) | ((shz & 1) << 2);
+
+            // Prevent same parent from being stored again and invalidate cached child descriptor
+            h = 0.0;
+            descriptor = 0;
+        }
+    }
+
+    None
+}
+
+/// Walks through the chunks of the visible layers along the ray with a 3D DDA like world_raymarch() in raymarch.glsl
+pub fn world_raymarch(world: &World, o: Vector3<f32>, mut d: Vector3<f32>) -> Option<Hit> {
+    let (lower_chunk, upper_chunk) = world.chunk_range()?;
+    let size = world.chunk_size() as f32;
+
+    // Get rid of small ray direction components to avoid division by zero
+    for i in 0..3 {
+        d[i] = if d[i].abs() > EPS { d[i] } else if d[i] >= 0.0 { EPS } else { -EPS };
+    }
+
+    // Clip the ray with the box around all chunks
+    let t_lower = (lower_chunk.cast::<f32>() * size - o).component_div(&d);
+    let t_upper = ((upper_chunk.add_scalar(1)).cast::<f32>() * size - o).component_div(&d);
+    let t_near = t_lower.zip_map(&t_upper, min);
+    let t_far = t_lower.zip_map(&t_upper, max);
+    let t_min = max(max(max(t_near.x, t_near.y), t_near.z), 0.0);
+    let t_max = min(min(t_far.x, t_far.y), t_far.z);
+
+    if t_min > t_max {
+        return None;
+    }
+
+    let mut cell = ((o + t_min * d) / size).map(|x| x.floor() as i32).zip_zip_map(&lower_chunk, &upper_chunk, |x, lower, upper| x.clamp(lower, upper));
+    let cell_step = d.map(|x| x.signum() as i32);
+    let t_delta = d.map(|x| (size / x).abs());
+    let mut t_next = (cell + cell_step.map(|x| x.max(0))).cast::<f32>() * size;
+    t_next = (t_next - o).component_div(&d);
+
+    while (0..3).all(|i| cell[i] >= lower_chunk[i] && cell[i] <= upper_chunk[i]) {
+        let mut nearest: Option<Hit> = None;
+
+        for (layer, _) in world.layers().iter().enumerate().filter(|(_, layer)| layer.visible) {
+            let Some(chunk) = world.chunk(cell, layer) else {
+                continue;
+            };
+
+            // Chunk octrees are only moved, so the ray parameter stays the same
+            if let Some(hit) = octree_raymarch(&chunk.octree, chunk.to_local(o), d) {
+                if nearest.as_ref().is_none_or(|nearest| hit.t < nearest.t) {
+                    let pos = chunk.to_world(hit.pos);
+
+                    nearest = Some(Hit {
+                        pos,
+                        local_pos: pos,
+                        layer,
+                        ..hit
+                    });
+                }
+            }
+        }
+
+        if nearest.is_some() {
+            return nearest;
+        }
+
+        // Step into the neighbour across the nearest chunk side
+        let axis = if t_next.x < t_next.y && t_next.x < t_next.z {
+            0
+        } else if t_next.y < t_next.z {
+            1
+        } else {
+            2
+        };
+
+        cell[axis] += cell_step[axis];
+        t_next[axis] += t_delta[axis];
+    }
+
+    None
+}
+
+fn box_raymarch(lower: Vector3<f32>, upper: Vector3<f32>, o: Vector3<f32>, d: Vector3<f32>, t_max: f32) -> bool {
+    let t_lower = (lower - o).component_div(&d);
+    let t_upper = (upper - o).component_div(&d);
+    let t_near = t_lower.zip_map(&t_upper, min);
+    let t_far = t_lower.zip_map(&t_upper, max);
+    let t_min = max(max(max(t_near.x, t_near.y), t_near.z), 0.0);
+
+    t_min <= min(min(min(t_far.x, t_far.y), t_far.z), t_max)
+}
+
+/// Traverses the model of the instance in model coordinates, only hits nearer than t_max are returned
+pub fn instance_raymarch(world: &World, index: usize, o: Vector3<f32>, d: Vector3<f32>, t_max: f32) -> Option<Hit> {
+    let scene = world.scene();
+    let instance = &scene.instances()[index];
+    let model = &scene.models()[instance.model];
+    // Rotations are orthogonal, so the inverse is the transpose
+    let to_world = instance.rotation.cast::<f32>();
+    let to_model = to_world.transpose();
+    let model_center = scene.model_center(instance);
+    let world_center = scene.world_center(instance);
+
+    // Rotation keeps lengths, so the ray parameter is the same in both coordinate systems
+    let hit = octree_raymarch(model, to_model * (o - world_center) + model_center, to_model * d)
+        .filter(|hit| hit.t < t_max)?;
+
+    Some(Hit {
+        pos: scene.to_world(instance, hit.pos),
+        normal: (to_world * hit.normal.cast::<f32>()).map(|x| x as i32),
+        t: hit.t,
+        local_pos: hit.pos - model.bounds.min(),
+        instance: Some(index),
+        layer: 0,
+    })
+}
+
+/// Finds the nearest instance hit by the ray with the bounding volume hierarchy like scene_raymarch()
+/// in raymarch.glsl, skipping everything further away than t_max
+pub fn scene_raymarch(world: &World, o: Vector3<f32>, mut d: Vector3<f32>, t_max: f32) -> Option<Hit> {
+    let bvh = world.scene().bvh();
+
+    if bvh.is_empty() {
+        return None;
+    }
+
+    // Get rid of small ray direction components to avoid division by zero
+    for i in 0..3 {
+        d[i] = if d[i].abs() > EPS { d[i] } else if d[i] >= 0.0 { EPS } else { -EPS };
+    }
+
+    let mut nodes = Vec::with_capacity(BVH_STACK_SIZE);
+    let mut nearest: Option<Hit> = None;
+
+    nodes.push(0);
+
+    while let Some(node) = nodes.pop() {
+        let BvhNode { lower, upper, left, right, instance } = bvh[node as usize];
+        let t_max = nearest.as_ref().map_or(t_max, |hit| hit.t);
+
+        if !box_raymarch(lower.xyz(), upper.xyz(), o, d, t_max) {
+            continue;
+        }
+
+        if instance >= 0 {
+            if let Some(hit) = instance_raymarch(world, instance as usize, o, d, t_max) {
+                nearest = Some(hit);
+            }
+        } else {
+            nodes.push(left);
+            nodes.push(right);
+        }
+    }
+
+    nearest
+}
+
+/// Nearest voxel of either the chunks or the instances
+pub fn raymarch(world: &World, o: Vector3<f32>, d: Vector3<f32>) -> Option<Hit> {
+    let hit = world_raymarch(world, o, d);
+    let t_max = hit.as_ref().map_or(f32::MAX, |hit| hit.t);
+
+    scene_raymarch(world, o, d, t_max).or(hit)
+}
+
+/// Same as ground_raymarch() in raymarch.glsl, returns the voxel right under the ground, the hit point
+/// and the ray parameter at it
+pub fn ground_raymarch(world: &World, o: Vector3<f32>, d: Vector3<f32>) -> Option<(Vector3<f32>, Vector3<f32>, f32)> {
+    let ground = world.chunk_range().map_or(0.0, |(lower, _)| (lower.z * world.chunk_size() as i32) as f32);
+
+    if d.z >= 0.0 || o.z <= ground {
+        return None;
+    }
+
+    let t = (ground - o.z) / d.z;
+    let hit_coord = o + t * d;
+    let hit_pos = Vector3::new(hit_coord.x.floor(), hit_coord.y.floor(), ground - 1.0);
+
+    Some((hit_pos, hit_coord, t))
+}
+
+// Same as cursor_color() in render.comp
+fn cursor_color(mouse: &MouseState) -> Vector3<f32> {
+    if mouse.tool == Tool::Eraser as u32 {
+        Vector3::new(199.0, 32.0, 32.0)
+    } else if mouse.tool == Tool::Eyedropper as u32 {
+        Vector3::new(255.0, 255.0, 255.0)
+    } else {
+        unpack_color(mouse.color) * 0.5
+    }
+}
+
+// Same as unpack_color() in world.glsl
+fn unpack_color(color: u32) -> Vector3<f32> {
+    Vector3::new(color >> 16 & 0xFF, color >> 8 & 0xFF, color & 0xFF).cast::<f32>()
+}
+
+// Same as shade() in render.comp
+pub fn shade(world: &World, cursor: &Cursor, mouse: &MouseState, o: Vector3<f32>, d: Vector3<f32>) -> Vector3<f32> {
+    let background = Vector3::new(32.0, 32.0, 32.0);
+    let cursor_color = cursor_color(mouse);
+    let hit = raymarch(world, o, d);
+    let ground_hit = ground_raymarch(world, o, d);
+
+    // Instances can be placed under the ground, so the nearer of the two is shown
+    if let Some(hit) = hit.filter(|hit| ground_hit.is_none_or(|(_, _, t)| hit.t < t)) {
+        // Render cursor
+        if hit.normal == cursor.normal.xyz() && hit.pos == cursor.pos.xyz() && instance_index(hit.instance) == cursor.instance {
+            return cursor_color;
+        }
+
+        let brightness = if hit.normal.x != 0 { 0.7 } else if hit.normal.y != 0 { 0.75 } else { 1.0 };
+
+        let color = match hit.instance {
+            None => unpack_color(world.color(hit.layer, hit.pos)),
+            Some(_) => unpack_color(DEFAULT_COLOR),
+        };
+
+        return color * brightness;
+    }
+
+    if let Some((pos, ground_coord, _)) = ground_hit {
+        // Render cursor
+        if pos == cursor.pos.xyz() && cursor.normal.xyz() == Vector3::new(0, 0, 1) && cursor.instance < 0 {
+            return cursor_color;
+        }
+
+        // Render chunk borders under the chunks in use
+        if let Some((lower_chunk, upper_chunk)) = world.chunk_range() {
+            let size = world.chunk_size() as f32;
+            let border_thickness = 0.025;
+
+            let border_hit = (0..2).any(|i| {
+                let border_distance = (ground_coord[i] - (ground_coord[i] / size).round() * size).abs();
+
+                border_distance < border_thickness
+            });
+            let inside = (0..2).all(|i| {
+                ground_coord[i] >= lower_chunk[i] as f32 * size && ground_coord[i] <= (upper_chunk[i] + 1) as f32 * size
+            });
+
+            if inside && border_hit {
+                return Vector3::new(188.0, 190.0, 194.0);
+            }
+        }
+    }
+
+    background
+}
+
+// Same as raycast_mouse.comp
+pub fn raycast_mouse(world: &World, camera: &CameraProjection, coordinate: Vector2<f32>) -> Cursor {
+    let ray = Ray::new(camera, coordinate.x, coordinate.y);
+    let hit = raymarch(world, ray.origin, ray.direction);
+    let ground_hit = ground_raymarch(world, ray.origin, ray.direction);
+
+    if let Some(hit) = hit.filter(|hit| ground_hit.is_none_or(|(_, _, t)| hit.t < t)) {
+        Cursor {
+            pos: hit.pos.to_homogeneous(),
+            normal: hit.normal.to_homogeneous(),
+            local_pos: hit.local_pos,
+            instance: instance_index(hit.instance),
+        }
+    } else if let Some((pos, _, _)) = ground_hit {
+        Cursor {
+            pos: pos.to_homogeneous(),
+            normal: Vector4::new(0, 0, 1, 0),
+            local_pos: pos,
+            instance: -1,
+        }
+    } else {
+        // Zero normal means there is no cursor
+        Cursor::default()
+    }
+}
+
+// Same as main() in edit.comp, but the voxel is returned instead of changed. It's filled with w 1,
+// cleared with w -1, painted with w 2 and left alone with w 0.
+pub fn edited_voxel(world: &World, cursor: &Cursor, mouse: &MouseState) -> Vector4<f32> {
+    let normal = cursor.normal.xyz();
+
+    // Zero normal means there is no cursor, and locked layers can't be edited
+    if normal == Vector3::zeros() || world.editable_layer().is_none() {
+        return Vector4::zeros();
+    }
+
+    let left_button = mouse.left_button == vk::TRUE;
+
+    if left_button && mouse.tool == Tool::Pencil as u32 {
+        (cursor.pos.xyz() + normal.cast::<f32>()).push(1.0)
+    } else if (left_button && mouse.tool == Tool::Eraser as u32 || mouse.right_button == vk::TRUE) && cursor.instance < 0 {
+        // Instances only show their models, so there is nothing to erase in the world under them
+        cursor.pos.xyz().push(-1.0)
+    } else if left_button && mouse.tool == Tool::Paint as u32 && cursor.instance < 0 {
+        cursor.pos.xyz().push(2.0)
+    } else {
+        Vector4::zeros()
+    }
+}
+
+// Same as selection_outline() in render.comp
+pub fn selection_outline(selection: Option<&Selection>, o: Vector3<f32>, mut d: Vector3<f32>) -> bool {
+    let Some(selection) = selection else {
+        return false;
+    };
+
+    let lower = selection.lower().cast::<f32>();
+    let upper = selection.upper().add_scalar(1).cast::<f32>();
+    let edge_thickness = 0.05;
+
+    // Get rid of small ray direction components to avoid division by zero
+    for i in 0..3 {
+        d[i] = if d[i].abs() > EPS { d[i] } else if d[i] >= 0.0 { EPS } else { -EPS };
+    }
+
+    let t_lower = (lower - o).component_div(&d);
+    let t_upper = (upper - o).component_div(&d);
+    let t_near = t_lower.zip_map(&t_upper, min);
+    let t_far = t_lower.zip_map(&t_upper, max);
+    let t_min = max(max(t_near.x, t_near.y), t_near.z);
+    let t_max = min(min(t_far.x, t_far.y), t_far.z);
+
+    if t_min > t_max {
+        return false;
+    }
+
+    // Point on a side of the box is on an edge when it's close to another side as well
+    [t_min, t_max].into_iter().any(|t| {
+        let point = o + t * d;
+        let close_sides = (0..3)
+            .filter(|i| min((point[*i] - lower[*i]).abs(), (point[*i] - upper[*i]).abs()) < edge_thickness)
+            .count();
+
+        t >= 0.0 && close_sides >= 2
+    })
+}
+
+/// Renders the whole image the same way render.comp does for every pixel and returns it
+/// as RGB8 rows. The shader swaps red and blue for the BGRA swapchain, which isn't needed here.
+pub fn render(world: &World, camera: &CameraProjection, cursor: &Cursor, mouse: &MouseState, selection: Option<&Selection>, width: u32, height: u32) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity((width * height * 3) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            let u = x as f32 / width as f32;
+            let v = y as f32 / height as f32;
+            let ray = Ray::new(camera, u, v);
+            let color = match selection_outline(selection, ray.origin, ray.direction) {
+                true => Vector3::new(255.0, 214.0, 0.0),
+                false => shade(world, cursor, mouse, ray.origin, ray.direction),
+            } / 255.0;
+
+            pixels.extend(color.iter().map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8));
+        }
+    }
+
+    pixels
+}
+
+// GLSL min() and max() return the first argument unless the second one compares lower (greater)
+fn min(x: f32, y: f32) -> f32 {
+    if y < x { y } else { x }
+}
+
+fn max(x: f32, y: f32) -> f32 {
+    if x < y { y } else { x }
+}
+
+// Instances are counted from 0 in the shaders and -1 stands for no instance
+fn instance_index(instance: Option<usize>) -> i32 {
+    instance.map_or(-1, |index| index as i32)
+}
+
+// GLSL findMSB() returns -1 for zero, which wraps around when stored in a uint
+fn find_msb(value: u32) -> u32 {
+    31_u32.wrapping_sub(value.leading_zeros())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Matrix3;
+    use crate::{
+        camera::Camera,
+        world::Layer,
+        scene::{
+            Instance,
+            quarter_turns
+        },
+    };
+
+    // Depth 3 octree resides at [8, 16]
+    fn octree() -> Octree {
+        Octree::new(3, vec![Vector3::new(10.0, 11.0, 12.0), Vector3::new(10.0, 11.0, 14.0)])
+    }
+
+    fn assert_hit(hit: Option<Hit>, pos: [f32; 3], normal: [i32; 3], t: f32) {
+        let hit = hit.expect("Ray should hit a voxel");
+
+        assert_eq!(hit.pos, Vector3::from(pos));
+        assert_eq!(hit.normal, Vector3::from(normal));
+        assert!((hit.t - t).abs() < 1e-4, "t is {} instead of {}", hit.t, t);
+    }
+
+    #[test]
+    fn octree_raymarch_hits_nearest_voxel_side() {
+        let octree = octree();
+
+        assert_hit(octree_raymarch(&octree, Vector3::new(10.5, 11.5, 0.0), Vector3::z()), [10.0, 11.0, 12.0], [0, 0, -1], 12.0);
+        assert_hit(octree_raymarch(&octree, Vector3::new(10.5, 11.5, 20.0), -Vector3::z()), [10.0, 11.0, 14.0], [0, 0, 1], 5.0);
+        assert_hit(octree_raymarch(&octree, Vector3::new(0.0, 11.5, 12.5), Vector3::x()), [10.0, 11.0, 12.0], [-1, 0, 0], 10.0);
+        assert_hit(octree_raymarch(&octree, Vector3::new(10.5, 20.0, 14.5), -Vector3::y()), [10.0, 11.0, 14.0], [0, 1, 0], 8.0);
+
+        let diagonal = Vector3::new(1.0, 1.0, 1.0).normalize();
+        assert_hit(octree_raymarch(&octree, Vector3::new(8.2, 9.5, 10.5), diagonal), [10.0, 11.0, 12.0], [-1, 0, 0], 1.8 * 3.0_f32.sqrt());
+    }
+
+    #[test]
+    fn octree_raymarch_starts_inside_the_octree() {
+        let octree = octree();
+
+        assert_hit(octree_raymarch(&octree, Vector3::new(10.5, 11.5, 9.5), Vector3::z()), [10.0, 11.0, 12.0], [0, 0, -1], 2.5);
+        assert_hit(octree_raymarch(&octree, Vector3::new(10.5, 11.5, 13.5), Vector3::z()), [10.0, 11.0, 14.0], [0, 0, -1], 0.5);
+        assert_hit(octree_raymarch(&octree, Vector3::new(15.5, 11.5, 12.5), -Vector3::x()), [10.0, 11.0, 12.0], [1, 0, 0], 4.5);
+
+        // Ray starting in a voxel hits it right away
+        let hit = octree_raymarch(&octree, Vector3::new(10.5, 11.5, 14.5), Vector3::z()).unwrap();
+        assert_eq!((hit.pos, hit.t), (Vector3::new(10.0, 11.0, 14.0), 0.0));
+    }
+
+    #[test]
+    fn octree_raymarch_misses() {
+        let octree = octree();
+
+        assert!(octree_raymarch(&octree, Vector3::new(10.5, 11.5, 0.0), -Vector3::z()).is_none());
+        assert!(octree_raymarch(&octree, Vector3::new(11.5, 11.5, 0.0), Vector3::z()).is_none());
+        assert!(octree_raymarch(&octree, Vector3::new(0.0, 0.0, 0.0), Vector3::x()).is_none());
+        assert!(octree_raymarch(&Octree::new(3, Vec::new()), Vector3::new(10.5, 11.5, 0.0), Vector3::z()).is_none());
+    }
+
+    #[test]
+    fn world_raymarch_crosses_chunks() {
+        let mut world = World::new(3);
+        world.insert(0, Vector3::new(0.0, 5.0, 5.0));
+        world.insert(0, Vector3::new(9.0, 1.0, 1.0));
+        world.insert(0, Vector3::new(-3.0, -2.0, -1.0));
+
+        assert_hit(world_raymarch(&world, Vector3::new(-5.0, 1.5, 1.5), Vector3::x()), [9.0, 1.0, 1.0], [-1, 0, 0], 14.0);
+        assert_hit(world_raymarch(&world, Vector3::new(20.0, 1.5, 1.5), -Vector3::x()), [9.0, 1.0, 1.0], [1, 0, 0], 10.0);
+        assert_hit(world_raymarch(&world, Vector3::new(-2.5, -1.5, 10.0), -Vector3::z()), [-3.0, -2.0, -1.0], [0, 0, 1], 10.0);
+        assert_hit(world_raymarch(&world, Vector3::new(0.5, 4.5, 5.5), Vector3::y()), [0.0, 5.0, 5.0], [0, -1, 0], 0.5);
+        assert!(world_raymarch(&world, Vector3::new(-5.0, 2.5, 1.5), Vector3::x()).is_none());
+        assert!(world_raymarch(&World::new(3), Vector3::zeros(), Vector3::x()).is_none());
+    }
+
+    #[test]
+    fn world_raymarch_skips_hidden_layers() {
+        let mut world = World::new(3);
+        let hidden = world.add_layer(Layer::new("Hidden")).unwrap();
+        world.insert(hidden, Vector3::new(2.0, 1.0, 1.0));
+        world.insert(0, Vector3::new(5.0, 1.0, 1.0));
+
+        assert_hit(world_raymarch(&world, Vector3::new(-5.0, 1.5, 1.5), Vector3::x()), [2.0, 1.0, 1.0], [-1, 0, 0], 7.0);
+
+        world.layer_mut(hidden).visible = false;
+
+        assert_hit(world_raymarch(&world, Vector3::new(-5.0, 1.5, 1.5), Vector3::x()), [5.0, 1.0, 1.0], [-1, 0, 0], 10.0);
+    }
+
+    #[test]
+    fn render_shades_voxel_colors() {
+        let mut world = World::new(3);
+        world.insert(0, Vector3::new(1.0, 1.0, 1.0));
+        world.set_color(0, Vector3::new(1.0, 1.0, 1.0), 0xFF8000);
+
+        // Second of 2x2 pixels looks along the center of the view
+        let mut camera = Camera::new(Vector3::new(1.5, -8.0, 1.5), Vector3::new(1.5, 1.5, 1.5));
+        camera.set_aspect_ratio(1.0);
+        let pixels = render(&world, &camera.projection(), &Cursor::default(), &MouseState::default(), None, 2, 2);
+
+        // Sides facing along Y are shaded with 0.75
+        assert_eq!(pixels[9..12], [191, 96, 0]);
+
+        let mut camera = Camera::new(Vector3::new(1.5, -8.0, 30.0), Vector3::new(1.5, -20.0, 40.0));
+        camera.set_aspect_ratio(1.0);
+        let pixels = render(&world, &camera.projection(), &Cursor::default(), &MouseState::default(), None, 2, 2);
+
+        assert_eq!(pixels, [32; 12]);
+    }
+
+    // Cursor under the center of a square view
+    fn cursor(world: &World, look_from: Vector3<f32>, look_at: Vector3<f32>) -> Cursor {
+        let mut camera = Camera::new(look_from, look_at);
+        camera.set_aspect_ratio(1.0);
+
+        raycast_mouse(world, &camera.projection(), Vector2::new(0.5, 0.5))
+    }
+
+    fn click(tool: Tool) -> MouseState {
+        let mut mouse = MouseState::default();
+        mouse.left_button = vk::TRUE;
+        mouse.tool = tool as u32;
+
+        mouse
+    }
+
+    #[test]
+    fn raycast_mouse_hits_voxel_side() {
+        let mut world = World::new(3);
+        world.insert(0, Vector3::new(1.0, 1.0, 1.0));
+        world.insert(0, Vector3::new(1.0, 4.0, 1.0));
+
+        let cursor = cursor(&world, Vector3::new(1.5, -8.0, 1.5), Vector3::new(1.5, 1.5, 1.5));
+
+        assert_eq!(cursor.pos, Vector4::new(1.0, 1.0, 1.0, 0.0));
+        assert_eq!(cursor.normal, Vector4::new(0, -1, 0, 0));
+        assert_eq!(cursor.instance, -1);
+
+        // Pencil adds next to the side, eraser removes the voxel itself
+        assert_eq!(edited_voxel(&world, &cursor, &click(Tool::Pencil)), Vector4::new(1.0, 0.0, 1.0, 1.0));
+        assert_eq!(edited_voxel(&world, &cursor, &click(Tool::Eraser)), Vector4::new(1.0, 1.0, 1.0, -1.0));
+    }
+
+    #[test]
+    fn raycast_mouse_hits_ground() {
+        let mut world = World::new(3);
+        world.insert(0, Vector3::new(5.0, 5.0, 5.0));
+
+        let cursor = cursor(&world, Vector3::new(1.5, -8.0, 10.0), Vector3::new(1.5, 1.5, 0.0));
+
+        // Cursor is on the voxel right under the ground, so the pencil fills the one on it
+        assert_eq!(cursor.pos, Vector4::new(1.0, 1.0, -1.0, 0.0));
+        assert_eq!(cursor.normal, Vector4::new(0, 0, 1, 0));
+        assert_eq!(edited_voxel(&world, &cursor, &click(Tool::Pencil)), Vector4::new(1.0, 1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn raycast_mouse_misses() {
+        let mut world = World::new(3);
+        world.insert(0, Vector3::new(1.0, 1.0, 1.0));
+
+        let cursor = cursor(&world, Vector3::new(1.5, -8.0, 1.5), Vector3::new(1.5, -20.0, 10.0));
+
+        assert_eq!(cursor.normal, Vector4::zeros());
+        assert_eq!(edited_voxel(&world, &cursor, &click(Tool::Pencil)), Vector4::zeros());
+        assert_eq!(edited_voxel(&world, &cursor, &click(Tool::Eraser)), Vector4::zeros());
+    }
+    // Instances of a depth 2 model with three voxels, which resides at [4, 8]
+    fn world_with_instances(instances: &[(Vector3<i32>, Matrix3<i32>)]) -> World {
+        let mut world = World::new(3);
+        let voxels = vec![Vector3::new(4.0, 4.0, 4.0), Vector3::new(5.0, 4.0, 4.0), Vector3::new(4.0, 5.0, 4.0)];
+        let model = world.add_model(Octree::new(2, voxels)).unwrap();
+
+        for (translation, rotation) in instances {
+            world.add_instance(Instance::new(model, *translation, *rotation)).unwrap();
+        }
+
+        world
+    }
+
+    #[test]
+    fn raycast_mouse_picks_voxels_of_rotated_instances() {
+        let world = world_with_instances(&[(Vector3::new(10, 0, 2), quarter_turns(2, 1))]);
+
+        // Quarter turn around z moves model x along world y and model y against world x
+        let top = cursor(&world, Vector3::new(13.5, 1.5, 20.0), Vector3::new(13.5, 1.5, 0.0));
+        assert_eq!(top.pos, Vector4::new(13.0, 1.0, 2.0, 0.0));
+        assert_eq!(top.normal, Vector4::new(0, 0, 1, 0));
+        assert_eq!((top.local_pos, top.instance), (Vector3::new(1.0, 0.0, 0.0), 0));
+
+        let top = cursor(&world, Vector3::new(12.5, 0.5, 20.0), Vector3::new(12.5, 0.5, 0.0));
+        assert_eq!(top.pos, Vector4::new(12.0, 0.0, 2.0, 0.0));
+        assert_eq!((top.local_pos, top.instance), (Vector3::new(0.0, 1.0, 0.0), 0));
+
+        // Side of the rotated model is rotated too
+        let side = cursor(&world, Vector3::new(20.0, 1.5, 2.5), Vector3::new(0.0, 1.5, 2.5));
+        assert_eq!(side.pos, Vector4::new(13.0, 1.0, 2.0, 0.0));
+        assert_eq!(side.normal, Vector4::new(1, 0, 0, 0));
+    }
+
+    #[test]
+    fn scene_raymarch_finds_the_nearest_instance_like_testing_all_of_them() {
+        let instances = (0..12)
+            .map(|i| (Vector3::new((i % 4) * 6, (i / 4) * 6, (i % 3) * 3), quarter_turns(i as usize % 3, i)))
+            .collect::<Vec<_>>();
+        let world = world_with_instances(&instances);
+        let mut seed = 1_u32;
+        let mut random = || {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            (seed >> 8) as f32 / (1 << 24) as f32
+        };
+        let mut hits = 0;
+
+        for _ in 0..500 {
+            let o = Vector3::new(random() * 60.0 - 20.0, random() * 60.0 - 20.0, random() * 40.0 - 10.0);
+            let target = Vector3::new(random() * 24.0, random() * 18.0, random() * 10.0);
+            let d = (target - o).normalize();
+
+            let nearest = (0..instances.len())
+                .filter_map(|index| instance_raymarch(&world, index, o, d, f32::MAX))
+                .min_by(|a, b| a.t.total_cmp(&b.t));
+
+            match (scene_raymarch(&world, o, d, f32::MAX), nearest) {
+                (Some(hit), Some(nearest)) => {
+                    assert_eq!((hit.pos, hit.instance), (nearest.pos, nearest.instance));
+                    assert!((hit.t - nearest.t).abs() < 1e-4);
+                    hits += 1;
+                },
+                (None, None) => (),
+                _ => panic!("Ray from {:?} along {:?} hits only with one of them", o, d),
+            }
+        }
+
+        assert!(hits > 50, "Only {} rays hit", hits);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,202 @@
+use std::{
+    fs::{
+        self,
+        File
+    },
+    io::{
+        self,
+        BufWriter,
+        Write
+    },
+    path::Path,
+};
+use winit::{
+    dpi::{
+        PhysicalPosition,
+        PhysicalSize
+    },
+    event::{
+        ElementState,
+        ModifiersState
+    },
+};
+use super::{
+    format::invalid_data,
+    editor::Event,
+    input::Input,
+};
+
+// Recordings are text, one record a line after this header, starting with seconds since recording started
+const HEADER: &str = "voxel_editor recording 1";
+
+#[derive(PartialEq)]
+pub enum Record {
+    Event(Event),
+    Frame, // Camera moves by the time since the last frame
+}
+
+/// Writes the events an editor handles and the frames it runs to a file as they happen
+pub struct Recorder {
+    file: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        writeln!(file, "{}", HEADER)?;
+
+        Ok(Self {
+            file,
+        })
+    }
+
+    pub fn write(&mut self, time: f64, record: &Record) -> io::Result<()> {
+        // Keys without a name can't be bound or typed into the console, so leaving them out changes nothing
+        if let Record::Event(Event::Input(input, _)) = record {
+            if Input::parse(&input.to_string()).is_err() {
+                return Ok(());
+            }
+        }
+
+        write!(self.file, "{} ", time)?;
+
+        match record {
+            Record::Event(Event::Resized(size)) => writeln!(self.file, "resize {} {}", size.width, size.height)?,
+            Record::Event(Event::CursorMoved(position)) => writeln!(self.file, "cursor {} {}", position.x, position.y)?,
+            Record::Event(Event::MouseMotion(x, y)) => writeln!(self.file, "motion {} {}", x, y)?,
+            Record::Event(Event::Input(input, ElementState::Pressed)) => writeln!(self.file, "press {}", input)?,
+            Record::Event(Event::Input(input, ElementState::Released)) => writeln!(self.file, "release {}", input)?,
+            Record::Event(Event::Scroll(lines)) => writeln!(self.file, "scroll {}", lines)?,
+            Record::Event(Event::Modifiers(modifiers)) => writeln!(self.file, "modifiers {}", modifiers.bits())?,
+            // Code point, since the character might be whitespace
+            Record::Event(Event::Character(character)) => writeln!(self.file, "character {}", *character as u32)?,
+            Record::Frame => writeln!(self.file, "frame")?,
+        }
+
+        // Recordings are made to find bugs, so they should survive a crash
+        if let Record::Frame = record {
+            self.file.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Records with the seconds since recording started
+pub fn load(path: &Path) -> io::Result<Vec<(f64, Record)>> {
+    let text = fs::read_to_string(path)?;
+    let mut lines = text.lines().enumerate();
+
+    if lines.next().map(|(_, line)| line) != Some(HEADER) {
+        return Err(invalid_data("not a recording"));
+    }
+
+    lines
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| parse_record(line).map_err(|message| invalid_data(&format!("line {}: {}", index + 1, message))))
+        .collect()
+}
+
+fn parse_record(line: &str) -> Result<(f64, Record), String> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    let time = match parse_number::<f64>(words[0])? {
+        time if time.is_finite() => time,
+        _ => return Err(format!("Invalid time '{}'", words[0])),
+    };
+
+    let record = match (words.get(1).copied().unwrap_or_default(), &words[2.min(words.len())..]) {
+        ("resize", [width, height]) => Record::Event(Event::Resized(PhysicalSize::new(parse_number(width)?, parse_number(height)?))),
+        ("cursor", [x, y]) => Record::Event(Event::CursorMoved(PhysicalPosition::new(parse_number(x)?, parse_number(y)?))),
+        ("motion", [x, y]) => Record::Event(Event::MouseMotion(parse_number(x)?, parse_number(y)?)),
+        ("press", [input]) => Record::Event(Event::Input(Input::parse(input)?, ElementState::Pressed)),
+        ("release", [input]) => Record::Event(Event::Input(Input::parse(input)?, ElementState::Released)),
+        ("scroll", [lines]) => Record::Event(Event::Scroll(parse_number(lines)?)),
+        ("modifiers", [bits]) => Record::Event(Event::Modifiers(ModifiersState::from_bits_truncate(parse_number(bits)?))),
+        ("character", [code]) => {
+            let character = char::from_u32(parse_number(code)?).ok_or_else(|| format!("Invalid character '{}'", code))?;
+
+            Record::Event(Event::Character(character))
+        },
+        ("frame", []) => Record::Frame,
+        _ => return Err(format!("Invalid record '{}'", line)),
+    };
+
+    Ok((time, record))
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid number '{}'", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::event::{
+        VirtualKeyCode,
+        MouseButton
+    };
+
+    #[test]
+    fn recorder_writes_what_load_reads() {
+        let path = std::env::temp_dir().join(format!("voxel_editor_recording_{}.txt", std::process::id()));
+        let records = [
+            (0.0, Record::Event(Event::Resized(PhysicalSize::new(640, 360)))),
+            (0.0, Record::Event(Event::CursorMoved(PhysicalPosition::new(320.5, 180.25)))),
+            (0.016, Record::Frame),
+            (0.25, Record::Event(Event::MouseMotion(-3.5, 12.0))),
+            (0.25, Record::Event(Event::Modifiers(ModifiersState::CTRL | ModifiersState::SHIFT))),
+            (0.5, Record::Event(Event::Input(Input::Key(VirtualKeyCode::Z), ElementState::Pressed))),
+            (0.5, Record::Event(Event::Input(Input::Mouse(MouseButton::Other(5)), ElementState::Released))),
+            (0.75, Record::Event(Event::Scroll(-1.5))),
+            (1.0, Record::Event(Event::Character(' '))),
+            (1.0, Record::Event(Event::Character('\t'))),
+            (1.0, Record::Event(Event::Character('é'))),
+            (1.5, Record::Frame),
+        ];
+
+        let mut recorder = Recorder::create(&path).unwrap();
+
+        for (time, record) in &records {
+            recorder.write(*time, record).unwrap();
+        }
+
+        // Keys without a name are left out
+        recorder.write(2.0, &Record::Event(Event::Input(Input::Key(VirtualKeyCode::Kana), ElementState::Pressed))).unwrap();
+        drop(recorder);
+
+        let loaded = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.len(), records.len());
+
+        for ((time, record), (loaded_time, loaded_record)) in records.iter().zip(&loaded) {
+            assert_eq!(time, loaded_time);
+            assert!(record == loaded_record, "record at {}", time);
+        }
+    }
+
+    #[test]
+    fn parse_record_rejects_invalid_lines() {
+        let lines = [
+            "NaN frame",
+            "inf frame",
+            "-inf cursor 1 2",
+            "soon frame",
+            "0.5",
+            "0.5 jump",
+            "0.5 frame 1",
+            "0.5 resize 640",
+            "0.5 resize -640 360",
+            "0.5 press Nope",
+            "0.5 modifiers ctrl",
+            "0.5 character 55296",
+        ];
+
+        for line in lines {
+            assert!(parse_record(line).is_err(), "{}", line);
+        }
+
+        assert!(matches!(parse_record("  2.5   frame "), Ok((time, Record::Frame)) if time == 2.5));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,257 @@
+use nalgebra::{
+    Matrix3,
+    Vector3,
+    Vector4
+};
+use super::octree::Octree;
+
+/// Rotation by a number of quarter turns counter-clockwise around an axis (0 is x, 1 is y and 2 is z)
+pub fn quarter_turns(axis: usize, turns: i32) -> Matrix3<i32> {
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let mut rotation = Matrix3::identity();
+
+    for _ in 0..turns.rem_euclid(4) {
+        let mut turn = Matrix3::identity();
+        turn[(u, u)] = 0;
+        turn[(v, v)] = 0;
+        turn[(u, v)] = -1;
+        turn[(v, u)] = 1;
+
+        rotation = turn * rotation;
+    }
+
+    rotation
+}
+
+/// Places a model into the world, rotated around the model's center
+#[derive(Clone)]
+pub struct Instance {
+    pub model: usize,
+    pub translation: Vector3<i32>, // World position of the lower corner of the placed model
+    pub rotation: Matrix3<i32>, // Made of quarter turns only, so the model stays aligned with the voxel grid
+}
+
+impl Instance {
+    pub fn new(model: usize, translation: Vector3<i32>, rotation: Matrix3<i32>) -> Self {
+        Self {
+            model,
+            translation,
+            rotation,
+        }
+    }
+}
+
+// Node of the bounding volume hierarchy over instances in the layout of BvhNode in world.glsl
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+pub struct BvhNode {
+    pub lower: Vector4<f32>,
+    pub upper: Vector4<f32>,
+    pub left: i32, // Child nodes, -1 for leaves
+    pub right: i32,
+    pub instance: i32, // Instance of a leaf, -1 for inner nodes
+}
+
+/// Models are octrees which can be placed into the world any number of times by instances
+#[derive(Default)]
+pub struct Scene {
+    models: Vec<Octree>,
+    instances: Vec<Instance>,
+    bvh: Vec<BvhNode>,
+}
+
+impl Scene {
+    pub fn models(&self) -> &[Octree] {
+        &self.models
+    }
+
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    /// Root is the first node, empty when there are no instances
+    pub fn bvh(&self) -> &[BvhNode] {
+        &self.bvh
+    }
+
+    pub fn add_model(&mut self, octree: Octree) -> usize {
+        self.models.push(octree);
+        self.models.len() - 1
+    }
+
+    pub fn add_instance(&mut self, instance: Instance) -> usize {
+        assert!(instance.model < self.models.len(), "Instance of a model which doesn't exist");
+
+        self.instances.push(instance);
+        self.build_bvh();
+        self.instances.len() - 1
+    }
+
+    /// Box taken by the instance in world coordinates, rotations around the center don't change it
+    pub fn bounds(&self, instance: &Instance) -> (Vector3<f32>, Vector3<f32>) {
+        let lower = instance.translation.cast::<f32>();
+        let size = self.models[instance.model].size() as f32;
+
+        (lower, lower.add_scalar(size))
+    }
+
+    /// Center of the model in the model's coordinates, which the instance rotates around
+    pub fn model_center(&self, instance: &Instance) -> Vector3<f32> {
+        let model = &self.models[instance.model];
+
+        (model.bounds.min() + model.bounds.max()) * 0.5
+    }
+
+    /// Center of the placed model in world coordinates
+    pub fn world_center(&self, instance: &Instance) -> Vector3<f32> {
+        let (lower, upper) = self.bounds(instance);
+
+        (lower + upper) * 0.5
+    }
+
+    /// World position of a voxel of the instance's model, given in model coordinates
+    pub fn to_world(&self, instance: &Instance, pos: Vector3<f32>) -> Vector3<f32> {
+        let rotation = instance.rotation.cast::<f32>();
+
+        // Rotate the center of the voxel, its lower corner depends on the rotation
+        (rotation * (pos.add_scalar(0.5) - self.model_center(instance)) + self.world_center(instance)).map(f32::floor)
+    }
+
+    /// World positions of the voxels placed by the instance
+    pub fn voxels(&self, instance: &Instance) -> Vec<Vector3<f32>> {
+        self.models[instance.model]
+            .voxels()
+            .into_iter()
+            .map(|pos| self.to_world(instance, pos))
+            .collect()
+    }
+
+    // Splits the instances at the median along the longest axis of their bounds until single instances are left
+    fn build_bvh(&mut self) {
+        let mut indices = (0..self.instances.len()).collect::<Vec<_>>();
+
+        self.bvh.clear();
+        self.build_node(&mut indices);
+    }
+
+    fn build_node(&mut self, indices: &mut [usize]) -> i32 {
+        let bounds = indices.iter().map(|index| self.bounds(&self.instances[*index])).collect::<Vec<_>>();
+        let lower = bounds.iter().fold(bounds[0].0, |lower, bound| lower.inf(&bound.0));
+        let upper = bounds.iter().fold(bounds[0].1, |upper, bound| upper.sup(&bound.1));
+        let node = self.bvh.len();
+
+        self.bvh.push(BvhNode {
+            lower: lower.to_homogeneous(),
+            upper: upper.to_homogeneous(),
+            left: -1,
+            right: -1,
+            instance: -1,
+        });
+
+        if let [index] = indices {
+            self.bvh[node].instance = *index as i32;
+            return node as i32;
+        }
+
+        let axis = (upper - lower).imax();
+        let middle = indices.len() / 2;
+
+        indices.sort_by(|a, b| {
+            let a = self.world_center(&self.instances[*a])[axis];
+            let b = self.world_center(&self.instances[*b])[axis];
+
+            a.total_cmp(&b)
+        });
+
+        let (left, right) = indices.split_at_mut(middle);
+        self.bvh[node].left = self.build_node(left);
+        self.bvh[node].right = self.build_node(right);
+
+        node as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Three voxels of a depth 2 model, which resides at [4, 8]
+    fn scene_with_instances(instances: &[(Vector3<i32>, Matrix3<i32>)]) -> Scene {
+        let mut scene = Scene::default();
+        let voxels = vec![Vector3::new(4.0, 4.0, 4.0), Vector3::new(5.0, 4.0, 4.0), Vector3::new(4.0, 5.0, 4.0)];
+        let model = scene.add_model(Octree::new(2, voxels));
+
+        for (translation, rotation) in instances {
+            scene.add_instance(Instance::new(model, *translation, *rotation));
+        }
+
+        scene
+    }
+
+    // Instances under the node, checking that the node's box holds their bounds
+    fn leaves(scene: &Scene, node: i32) -> Vec<usize> {
+        let BvhNode { lower, upper, left, right, instance } = scene.bvh()[node as usize];
+
+        let instances = match instance {
+            -1 => [leaves(scene, left), leaves(scene, right)].concat(),
+            instance => vec![instance as usize],
+        };
+
+        for index in &instances {
+            let (instance_lower, instance_upper) = scene.bounds(&scene.instances()[*index]);
+            assert!(lower.xyz() <= instance_lower && instance_upper <= upper.xyz());
+        }
+
+        instances
+    }
+
+    #[test]
+    fn quarter_turns_rotate_counter_clockwise() {
+        let x = Vector3::new(1, 0, 0);
+        let y = Vector3::new(0, 1, 0);
+        let z = Vector3::new(0, 0, 1);
+
+        assert_eq!(quarter_turns(0, 1) * y, z);
+        assert_eq!(quarter_turns(1, 1) * z, x);
+        assert_eq!(quarter_turns(2, 1) * x, y);
+        assert_eq!(quarter_turns(2, 2) * x, -x);
+        assert_eq!(quarter_turns(2, -1), quarter_turns(2, 3));
+        assert_eq!(quarter_turns(1, 4), Matrix3::identity());
+        assert_eq!(quarter_turns(0, 1) * quarter_turns(0, 1).transpose(), Matrix3::identity());
+    }
+
+    #[test]
+    fn to_world_rotates_around_the_model_center() {
+        let scene = scene_with_instances(&[
+            (Vector3::new(10, 0, 0), Matrix3::identity()),
+            (Vector3::new(10, 0, 0), quarter_turns(2, 1)),
+        ]);
+        let [unrotated, rotated] = [0, 1].map(|index| &scene.instances()[index]);
+
+        assert_eq!(scene.world_center(rotated), Vector3::new(12.0, 2.0, 2.0));
+        assert_eq!(scene.to_world(unrotated, Vector3::new(5.0, 4.0, 4.0)), Vector3::new(11.0, 0.0, 0.0));
+        assert_eq!(scene.to_world(rotated, Vector3::new(5.0, 4.0, 4.0)), Vector3::new(13.0, 1.0, 0.0));
+
+        // Rotation keeps the voxels inside the instance's bounds
+        for pos in scene.voxels(rotated) {
+            let (lower, upper) = scene.bounds(rotated);
+            assert!(lower <= pos && pos < upper, "{:?}", pos);
+        }
+    }
+
+    #[test]
+    fn build_bvh_holds_every_instance_once() {
+        let instances = (0..7)
+            .map(|i| (Vector3::new(i * 6, (i % 3) * 10, -(i % 2) * 8), quarter_turns(i as usize % 3, i)))
+            .collect::<Vec<_>>();
+        let scene = scene_with_instances(&instances);
+
+        let mut found = leaves(&scene, 0);
+        found.sort();
+        assert_eq!(found, (0..7).collect::<Vec<_>>());
+
+        // Every node but the root has a parent, which doesn't leave unused nodes in the buffer
+        assert_eq!(scene.bvh().len(), 2 * 7 - 1);
+        assert!(Scene::default().bvh().is_empty());
+    }
+}
\ No newline at end of file
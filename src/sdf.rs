@@ -0,0 +1,405 @@
+use nalgebra::{
+    Vector2,
+    Vector3,
+    Rotation3
+};
+use super::octree::Octree;
+
+// Every shape is a distance bound: it may underestimate the distance to the surface
+// but never overestimates it, which is what lets voxelization skip whole octants.
+pub enum Sdf {
+    Sphere {
+        radius: f32,
+    },
+    Cuboid {
+        half_extents: Vector3<f32>,
+    },
+    Torus {
+        major_radius: f32,
+        minor_radius: f32,
+    },
+    Capsule {
+        a: Vector3<f32>,
+        b: Vector3<f32>,
+        radius: f32,
+    },
+    Union(Box<Sdf>, Box<Sdf>),
+    Intersection(Box<Sdf>, Box<Sdf>),
+    Subtraction(Box<Sdf>, Box<Sdf>),
+    SmoothUnion(Box<Sdf>, Box<Sdf>, f32),
+    SmoothSubtraction(Box<Sdf>, Box<Sdf>, f32),
+    Translation(Box<Sdf>, Vector3<f32>),
+    Rotation(Box<Sdf>, Rotation3<f32>),
+    Scale(Box<Sdf>, f32),
+}
+
+impl Sdf {
+    pub fn sphere(radius: f32) -> Self {
+        Self::Sphere {
+            radius,
+        }
+    }
+
+    pub fn cuboid(half_extents: Vector3<f32>) -> Self {
+        Self::Cuboid {
+            half_extents,
+        }
+    }
+
+    /// Torus lying in the XY plane, around the Z axis which is up for the camera
+    pub fn torus(major_radius: f32, minor_radius: f32) -> Self {
+        Self::Torus {
+            major_radius,
+            minor_radius,
+        }
+    }
+
+    pub fn capsule(a: Vector3<f32>, b: Vector3<f32>, radius: f32) -> Self {
+        Self::Capsule {
+            a,
+            b,
+            radius,
+        }
+    }
+
+    pub fn union(self, other: Sdf) -> Self {
+        Self::Union(Box::new(self), Box::new(other))
+    }
+
+    pub fn intersect(self, other: Sdf) -> Self {
+        Self::Intersection(Box::new(self), Box::new(other))
+    }
+
+    pub fn subtract(self, other: Sdf) -> Self {
+        Self::Subtraction(Box::new(self), Box::new(other))
+    }
+
+    pub fn smooth_union(self, other: Sdf, smoothness: f32) -> Self {
+        Self::SmoothUnion(Box::new(self), Box::new(other), smoothness)
+    }
+
+    pub fn smooth_subtract(self, other: Sdf, smoothness: f32) -> Self {
+        Self::SmoothSubtraction(Box::new(self), Box::new(other), smoothness)
+    }
+
+    pub fn translate(self, offset: Vector3<f32>) -> Self {
+        Self::Translation(Box::new(self), offset)
+    }
+
+    pub fn rotate(self, rotation: Rotation3<f32>) -> Self {
+        Self::Rotation(Box::new(self), rotation)
+    }
+
+    pub fn scale(self, factor: f32) -> Self {
+        Self::Scale(Box::new(self), factor)
+    }
+
+    pub fn distance(&self, p: Vector3<f32>) -> f32 {
+        match self {
+            Self::Sphere { radius } => p.norm() - radius,
+            Self::Cuboid { half_extents } => {
+                let q = p.abs() - half_extents;
+                let outside = q.sup(&Vector3::zeros()).norm();
+                let inside = q.max().min(0.0);
+
+                outside + inside
+            },
+            Self::Torus { major_radius, minor_radius } => {
+                let q = Vector2::new(p.xy().norm() - major_radius, p.z);
+
+                q.norm() - minor_radius
+            },
+            Self::Capsule { a, b, radius } => {
+                let pa = p - a;
+                let ba = b - a;
+
+                // Capsule with both ends at the same point is a sphere
+                let h = match ba.norm_squared() {
+                    length if length > 0.0 => (pa.dot(&ba) / length).clamp(0.0, 1.0),
+                    _ => 0.0,
+                };
+
+                (pa - ba * h).norm() - radius
+            },
+            Self::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Self::Intersection(a, b) => a.distance(p).max(b.distance(p)),
+            Self::Subtraction(a, b) => a.distance(p).max(-b.distance(p)),
+            Self::SmoothUnion(a, b, k) => {
+                let (d1, d2) = (a.distance(p), b.distance(p));
+                let h = (0.5 + 0.5 * (d2 - d1) / k).clamp(0.0, 1.0);
+
+                d2 + (d1 - d2) * h - k * h * (1.0 - h)
+            },
+            Self::SmoothSubtraction(a, b, k) => {
+                let (d1, d2) = (a.distance(p), b.distance(p));
+                let h = (0.5 - 0.5 * (d1 + d2) / k).clamp(0.0, 1.0);
+
+                d1 + (-d2 - d1) * h + k * h * (1.0 - h)
+            },
+            Self::Translation(sdf, offset) => sdf.distance(p - offset),
+            Self::Rotation(sdf, rotation) => sdf.distance(rotation.inverse_transform_vector(&p)),
+            Self::Scale(sdf, factor) => sdf.distance(p / *factor) * factor,
+        }
+    }
+
+    /// Voxelizes the shape into an octree of the given depth. Shapes are expected in world
+    /// coordinates, so they have to be placed inside [2^depth, 2^(depth + 1)] to be visible.
+    pub fn voxelize(&self, depth: u32) -> Result<Octree, String> {
+        let mut octree = Octree::new(depth, Vec::new());
+        let root_pos = octree.bounds.min();
+        let root_size = octree.size();
+
+        match self.voxelize_octant(&mut octree, root_pos, root_size) {
+            true => Ok(octree),
+            false => Err(format!("Shape doesn't fit into an octree of depth {}", depth)),
+        }
+    }
+
+    // Returns false once the octree is full
+    fn voxelize_octant(&self, octree: &mut Octree, pos: Vector3<f32>, size: u32) -> bool {
+        // A voxel is solid when its center is inside the shape. The distance at the
+        // center of the octant decides for all voxel centers within `reach` of it at once.
+        let half_size = size as f32 * 0.5;
+        let reach = (half_size - 0.5) * 3.0_f32.sqrt();
+        let distance = self.distance(pos.add_scalar(half_size));
+
+        // Undefined distances like the ones of a sphere with a NaN radius are left empty
+        if !distance.is_finite() || distance > reach {
+            return true;
+        }
+
+        // Single voxels are decided by their center alone
+        if distance <= -reach || size == 1 {
+            return octree.fill(pos, size);
+        }
+
+        let half_size = size / 2;
+
+        (0..8).all(|idx| {
+            let offset = Vector3::new(idx & 1, idx >> 1 & 1, idx >> 2 & 1) * half_size;
+
+            self.voxelize_octant(octree, pos + nalgebra::convert::<Vector3<u32>, Vector3<f32>>(offset), half_size)
+        })
+    }
+}
+
+// Argument of a shape in an expression
+enum Arg {
+    Shape(Sdf),
+    Number(f32),
+}
+
+/// Parses a shape written like `smooth_union(sphere(6), translate(box(4, 4, 4), 6, 0, 0), 2)`.
+/// Shapes go before the numbers in the arguments, rotations are in degrees around X, Y and Z.
+pub fn parse(expression: &str) -> Result<Sdf, String> {
+    let mut tokens = tokenize(expression).into_iter();
+
+    let sdf = match parse_arg(&mut tokens)? {
+        Arg::Shape(sdf) => sdf,
+        Arg::Number(_) => return Err(String::from("Expected a shape, got a number")),
+    };
+
+    match tokens.next() {
+        Some(token) => Err(format!("Unexpected '{}' after the shape", token)),
+        None => Ok(sdf),
+    }
+}
+
+// Names, numbers, parentheses and commas
+fn tokenize(expression: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+
+    for c in expression.chars() {
+        if c.is_whitespace() || "(),".contains(c) {
+            if !token.is_empty() {
+                tokens.push(std::mem::take(&mut token));
+            }
+            if !c.is_whitespace() {
+                tokens.push(c.to_string());
+            }
+        } else {
+            token.push(c);
+        }
+    }
+
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+fn parse_arg(tokens: &mut impl Iterator<Item = String>) -> Result<Arg, String> {
+    let name = tokens.next().ok_or_else(|| String::from("Unexpected end of the shape"))?;
+
+    if let Ok(number) = name.parse::<f32>() {
+        return match number.is_finite() {
+            true => Ok(Arg::Number(number)),
+            false => Err(format!("Expected a finite number, got '{}'", name)),
+        };
+    }
+
+    if !name.chars().all(|c| c.is_ascii_alphabetic() || c == '_') {
+        return Err(format!("Unexpected '{}'", name));
+    }
+
+    if tokens.next().as_deref() != Some("(") {
+        return Err(format!("Expected '(' after '{}'", name));
+    }
+
+    let mut args = Vec::new();
+
+    loop {
+        args.push(parse_arg(tokens)?);
+
+        match tokens.next().as_deref() {
+            Some(",") => continue,
+            Some(")") => break,
+            _ => return Err(format!("Expected ',' or ')' in the arguments of '{}'", name)),
+        }
+    }
+
+    shape(&name, args).map(Arg::Shape)
+}
+
+fn shape(name: &str, args: Vec<Arg>) -> Result<Sdf, String> {
+    let mut shapes = Vec::new();
+    let mut numbers = Vec::new();
+
+    for arg in args {
+        match arg {
+            Arg::Shape(sdf) if numbers.is_empty() => shapes.push(sdf),
+            Arg::Shape(_) => return Err(format!("Shapes go before the numbers in '{}'", name)),
+            Arg::Number(number) => numbers.push(number),
+        }
+    }
+
+    let count = shapes.len();
+    let mut shapes = shapes.into_iter();
+    let mut next = || shapes.next().unwrap();
+
+    match (name, count, &numbers[..]) {
+        ("sphere", 0, &[radius]) => Ok(Sdf::sphere(radius)),
+        ("box", 0, &[x, y, z]) => Ok(Sdf::cuboid(Vector3::new(x, y, z))),
+        ("torus", 0, &[major_radius, minor_radius]) => Ok(Sdf::torus(major_radius, minor_radius)),
+        ("capsule", 0, &[ax, ay, az, bx, by, bz, radius]) => Ok(Sdf::capsule(Vector3::new(ax, ay, az), Vector3::new(bx, by, bz), radius)),
+        ("union", 2, &[]) => Ok(next().union(next())),
+        ("intersect", 2, &[]) => Ok(next().intersect(next())),
+        ("subtract", 2, &[]) => Ok(next().subtract(next())),
+        ("smooth_union", 2, &[smoothness]) if smoothness > 0.0 => Ok(next().smooth_union(next(), smoothness)),
+        ("smooth_subtract", 2, &[smoothness]) if smoothness > 0.0 => Ok(next().smooth_subtract(next(), smoothness)),
+        ("translate", 1, &[x, y, z]) => Ok(next().translate(Vector3::new(x, y, z))),
+        ("rotate", 1, &[x, y, z]) => Ok(next().rotate(Rotation3::from_euler_angles(x.to_radians(), y.to_radians(), z.to_radians()))),
+        ("scale", 1, &[factor]) if factor > 0.0 => Ok(next().scale(factor)),
+        ("sphere" | "box" | "torus" | "capsule" | "union" | "intersect" | "subtract" | "smooth_union"
+            | "smooth_subtract" | "translate" | "rotate" | "scale", _, _) => Err(format!("Invalid arguments for '{}'", name)),
+        _ => Err(format!("Unknown shape '{}'", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Voxels whose centers are inside the shape, sorted like the voxelized ones
+    fn sampled(sdf: &Sdf, depth: u32) -> Vec<Vector3<f32>> {
+        let size = 1 << depth;
+        let mut voxels = (size..2 * size)
+            .flat_map(|z| (size..2 * size).flat_map(move |y| (size..2 * size).map(move |x| Vector3::new(x, y, z).cast::<f32>())))
+            .filter(|pos| sdf.distance(pos.add_scalar(0.5)) <= 0.0)
+            .collect::<Vec<_>>();
+
+        sort(&mut voxels);
+        voxels
+    }
+
+    fn voxelized(sdf: &Sdf, depth: u32) -> Vec<Vector3<f32>> {
+        let mut voxels = sdf.voxelize(depth).unwrap().voxels();
+
+        sort(&mut voxels);
+        voxels
+    }
+
+    fn sort(voxels: &mut [Vector3<f32>]) {
+        voxels.sort_by(|a, b| a.as_slice().partial_cmp(b.as_slice()).unwrap());
+    }
+
+    #[test]
+    fn voxelize_matches_sampling_voxel_centers() {
+        let center = Vector3::repeat(24.0);
+        let shapes = [
+            "sphere(6.3)",
+            "box(7.2, 3.4, 5.1)",
+            "subtract(box(6.6, 6.6, 6.6), sphere(7.7))",
+            "smooth_union(torus(4.6, 1.7), rotate(capsule(0, 0, -5.2, 0, 0, 5.3, 1.9), 30, 10, 0), 2.1)",
+            "intersect(scale(sphere(3.1), 2.3), translate(box(8, 8, 2.6), 0.3, 0, 1.2))",
+            "smooth_subtract(union(sphere(5.4), box(7.5, 1.3, 1.3)), torus(5.2, 1.4), 1.6)",
+        ];
+
+        for shape in shapes {
+            let sdf = parse(shape).unwrap().translate(center);
+            let voxels = voxelized(&sdf, 4);
+
+            assert!(!voxels.is_empty(), "{}", shape);
+            assert_eq!(voxels, sampled(&sdf, 4), "{}", shape);
+        }
+    }
+
+    #[test]
+    fn voxelize_fills_contained_octree() {
+        let octree = Sdf::sphere(100.0).voxelize(3).unwrap();
+
+        assert_eq!(octree.voxels().len(), 8 * 8 * 8);
+    }
+
+    #[test]
+    fn voxelize_fails_when_octree_is_full() {
+        assert!(Sdf::sphere(12.0).translate(Vector3::repeat(48.0)).voxelize(5).is_err());
+    }
+
+    #[test]
+    fn parse_builds_the_same_shape() {
+        let parsed = parse("translate(smooth_union(sphere(3), box(1, 2, 3), 0.5), 1, -2, 3.5)").unwrap();
+        let built = Sdf::sphere(3.0)
+            .smooth_union(Sdf::cuboid(Vector3::new(1.0, 2.0, 3.0)), 0.5)
+            .translate(Vector3::new(1.0, -2.0, 3.5));
+
+        for p in [Vector3::zeros(), Vector3::new(1.0, -2.0, 3.5), Vector3::new(4.0, 1.0, -2.0)] {
+            assert_eq!(parsed.distance(p), built.distance(p));
+        }
+    }
+
+    #[test]
+    fn parse_rejects_invalid_shapes() {
+        for shape in ["", "3", "sphere", "sphere(3", "sphere(3))", "sphere(1, 2)", "blob(1)", "scale(sphere(1), 0)", "union(1, sphere(1))", "translate(1, 2, 3, sphere(1))"] {
+            assert!(parse(shape).is_err(), "{}", shape);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_non_finite_numbers() {
+        for shape in ["sphere(nan)", "sphere(inf)", "box(1, -inf, 1)", "translate(sphere(1), 1e39, 0, 0)"] {
+            assert!(parse(shape).is_err(), "{}", shape);
+        }
+    }
+
+    #[test]
+    fn capsule_with_equal_ends_is_a_sphere() {
+        let center = Vector3::repeat(24.0);
+        let capsule = Sdf::capsule(center, center, 5.5);
+        let sphere = Sdf::sphere(5.5).translate(center);
+
+        for p in [center, Vector3::new(20.0, 26.0, 23.5), Vector3::zeros()] {
+            assert_eq!(capsule.distance(p), sphere.distance(p));
+        }
+
+        assert_eq!(voxelized(&capsule, 4), sampled(&sphere, 4));
+    }
+
+    #[test]
+    fn voxelize_leaves_undefined_distances_empty() {
+        assert!(Sdf::sphere(f32::NAN).voxelize(4).unwrap().voxels().is_empty());
+        assert!(Sdf::sphere(4.0).translate(Vector3::repeat(f32::NAN)).voxelize(4).unwrap().voxels().is_empty());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,178 @@
+use ash::vk;
+use std::{
+    io,
+    path::Path,
+};
+use nalgebra::{
+    Vector3,
+    Vector4
+};
+use super::{
+    format,
+    cursor::Cursor,
+    history::Edit,
+    world::{
+        World,
+        Layer
+    },
+};
+
+/// Axis-aligned box of voxels, both corners are inclusive
+#[derive(Clone, Copy)]
+pub struct Selection {
+    lower: Vector3<i32>,
+    upper: Vector3<i32>,
+}
+
+impl Selection {
+    /// Box spanning two voxels given in any order
+    pub fn new(a: Vector3<f32>, b: Vector3<f32>) -> Self {
+        let (a, b) = (a.map(|x| x.floor() as i32), b.map(|x| x.floor() as i32));
+
+        Self {
+            lower: a.inf(&b),
+            upper: a.sup(&b),
+        }
+    }
+
+    pub fn lower(&self) -> Vector3<i32> {
+        self.lower
+    }
+
+    pub fn upper(&self) -> Vector3<i32> {
+        self.upper
+    }
+
+    /// Amount of voxels in the box, saturating for boxes no world could hold
+    pub fn volume(&self) -> u64 {
+        (self.upper.cast::<i64>() - self.lower.cast::<i64>())
+            .iter()
+            .fold(1, |volume: u64, extent| volume.saturating_mul(*extent as u64 + 1))
+    }
+
+    pub fn voxels(&self) -> impl Iterator<Item = Vector3<f32>> {
+        let (lower, upper) = (self.lower, self.upper);
+
+        (lower.z..=upper.z).flat_map(move |z| {
+            (lower.y..=upper.y).flat_map(move |y| {
+                (lower.x..=upper.x).map(move |x| Vector3::new(x, y, z).cast::<f32>())
+            })
+        })
+    }
+
+    /// Fills every voxel of the selection in the active layer with a color
+    pub fn fill(&self, world: &mut World, edit: &mut Edit, color: u32) {
+        for pos in self.voxels() {
+            edit.set(world, pos, color);
+        }
+    }
+
+    /// Clears every voxel of the selection in the active layer
+    pub fn clear(&self, world: &mut World, edit: &mut Edit) {
+        for pos in self.voxels() {
+            edit.clear(world, pos);
+        }
+    }
+
+    /// Voxels of the active layer inside the selection with their colors, relative to its lower corner
+    pub fn copy(&self, world: &World) -> Clipboard {
+        let layer = world.active_layer();
+        let lower = self.lower.cast::<f32>();
+
+        Clipboard {
+            voxels: self.voxels()
+                .filter(|pos| world.contains(layer, *pos))
+                .map(|pos| (pos - lower, world.color(layer, pos)))
+                .collect(),
+        }
+    }
+
+    /// Moves the voxels of the active layer inside the selection and the selection itself by an offset
+    pub fn move_by(&mut self, world: &mut World, edit: &mut Edit, offset: Vector3<i32>) {
+        if world.editable_layer().is_none() {
+            return;
+        }
+
+        let clipboard = self.copy(world);
+
+        self.clear(world, edit);
+        self.lower += offset;
+        self.upper += offset;
+        clipboard.paste(world, edit, self.lower.cast::<f32>());
+    }
+
+    /// Saves the voxels of the active layer inside the selection as a scene file, they keep their positions
+    pub fn export(&self, world: &World, path: &Path) -> io::Result<()> {
+        let layer = &world.layers()[world.active_layer()];
+        let mut scene = World::with_layers(world.chunk_depth(), vec![Layer::new(&layer.name)]);
+
+        for pos in self.voxels().filter(|pos| world.contains(world.active_layer(), *pos)) {
+            if !scene.insert(0, pos) || !scene.set_color(0, pos, world.color(world.active_layer(), pos)) {
+                return Err(io::Error::other("selection doesn't fit into a scene"));
+            }
+        }
+
+        format::save(path, &scene)
+    }
+}
+
+/// Copied voxels relative to the lower corner of the selection they were copied from
+#[derive(Default)]
+pub struct Clipboard {
+    voxels: Vec<(Vector3<f32>, u32)>,
+}
+
+impl Clipboard {
+    /// Fills the copied voxels in the active layer with the lower corner of the copy placed at given voxel
+    pub fn paste(&self, world: &mut World, edit: &mut Edit, lower: Vector3<f32>) {
+        for (pos, color) in &self.voxels {
+            edit.set(world, lower + pos, *color);
+        }
+    }
+}
+
+/// Voxel the cursor points at, which for the ground is the empty voxel on top of it
+pub fn cursor_voxel(world: &World, cursor: &Cursor) -> Option<Vector3<f32>> {
+    let pos = cursor.pos.xyz();
+    let normal = cursor.normal.xyz();
+
+    // Zero normal means there is no cursor
+    if normal == Vector3::zeros() {
+        return None;
+    }
+
+    let hit_voxel = cursor.instance >= 0 || world.layers()
+        .iter()
+        .enumerate()
+        .any(|(index, layer)| layer.visible && world.contains(index, pos));
+
+    match hit_voxel {
+        true => Some(pos),
+        false => Some(pos + normal.cast::<f32>()),
+    }
+}
+
+// Selection in the layout of Selection in selection.glsl
+#[repr(C)]
+pub struct SelectionState {
+    lower: Vector4<i32>,
+    upper: Vector3<i32>,
+    enabled: vk::Bool32,
+}
+
+impl SelectionState {
+    pub fn new(selection: Option<&Selection>) -> Self {
+        match selection {
+            Some(selection) => Self {
+                lower: selection.lower.to_homogeneous(),
+                upper: selection.upper,
+                enabled: vk::TRUE,
+            },
+            None => Self {
+                lower: Vector4::zeros(),
+                upper: Vector3::zeros(),
+                enabled: vk::FALSE,
+            },
+        }
+    }
+}
\ No newline at end of file
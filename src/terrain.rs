@@ -0,0 +1,288 @@
+use nalgebra::{
+    Vector2,
+    Vector3
+};
+use super::octree::Octree;
+
+// Octaves past this are finer than a voxel and their amplitude gets lost in f32 precision
+pub const MAX_OCTAVES: u32 = 16;
+
+pub enum TerrainShape {
+    Fbm,
+    Ridged,
+}
+
+pub struct Terrain {
+    pub seed: u32,
+    pub scale: f32, // Size of the largest terrain features in voxels
+    pub octaves: u32,
+    pub min_height: f32, // Heights are measured in voxels from the bottom of the octree
+    pub max_height: f32,
+    pub shape: TerrainShape,
+    pub cave_threshold: Option<f32>, // Voxels with 3D noise above the threshold are carved out
+}
+
+impl Default for Terrain {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            scale: 16.0,
+            octaves: 4,
+            min_height: 2.0,
+            max_height: 12.0,
+            shape: TerrainShape::Fbm,
+            cave_threshold: None,
+        }
+    }
+}
+
+impl Terrain {
+    /// Height field filling an octree of the given depth, heights are cut off at its top
+    pub fn generate(&self, depth: u32) -> Result<Octree, String> {
+        let mut octree = Octree::new(depth, Vec::new());
+        let noise = GradientNoise::new(self.seed);
+        let origin = octree.bounds.min();
+        let size = octree.size();
+
+        for x in 0..size {
+            for y in 0..size {
+                let column = Vector2::new(x as f32, y as f32) / self.scale;
+                let height = match self.shape {
+                    TerrainShape::Fbm => noise.fbm_2d(column, self.octaves) * 0.5 + 0.5,
+                    TerrainShape::Ridged => noise.ridged_2d(column, self.octaves),
+                };
+                let height = self.min_height + (self.max_height - self.min_height) * height.clamp(0.0, 1.0);
+                let top = (height.round().max(0.0) as u32).min(size);
+
+                for z in 0..top {
+                    let offset = Vector3::new(x as f32, y as f32, z as f32);
+
+                    if let Some(threshold) = self.cave_threshold {
+                        // Caves use the same frequency as the surface so they line up with it
+                        if noise.fbm_3d(offset / self.scale, self.octaves) > threshold {
+                            continue;
+                        }
+                    }
+
+                    if !octree.insert(origin + offset) {
+                        return Err(format!("Terrain doesn't fit into an octree of depth {}", depth));
+                    }
+                }
+            }
+        }
+
+        Ok(octree)
+    }
+}
+
+// Improved Perlin noise with the permutation table shuffled by the seed
+pub struct GradientNoise {
+    permutation: [u8; 512],
+}
+
+impl GradientNoise {
+    pub fn new(seed: u32) -> Self {
+        let mut table = [0_u8; 256];
+
+        for (i, value) in table.iter_mut().enumerate() {
+            *value = i as u8;
+        }
+
+        // Fisher-Yates shuffle driven by xorshift32 (which must not start from zero)
+        let mut state = seed ^ 0x9E37_79B9;
+
+        for i in (1..table.len()).rev() {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            table.swap(i, state as usize % (i + 1));
+        }
+
+        let mut permutation = [0_u8; 512];
+
+        for (i, value) in permutation.iter_mut().enumerate() {
+            *value = table[i & 255];
+        }
+
+        Self {
+            permutation,
+        }
+    }
+
+    /// Noise value in range [-1, 1]
+    pub fn noise_2d(&self, p: Vector2<f32>) -> f32 {
+        let cell = p.map(f32::floor);
+        let local = p - cell;
+        let x = cell.x as i32 & 255;
+        let y = cell.y as i32 & 255;
+        let u = fade(local.x);
+        let v = fade(local.y);
+
+        let aa = self.hash(self.hash(x) + y);
+        let ab = self.hash(self.hash(x) + y + 1);
+        let ba = self.hash(self.hash(x + 1) + y);
+        let bb = self.hash(self.hash(x + 1) + y + 1);
+
+        let x1 = lerp(u, gradient_2d(aa, local.x, local.y), gradient_2d(ba, local.x - 1.0, local.y));
+        let x2 = lerp(u, gradient_2d(ab, local.x, local.y - 1.0), gradient_2d(bb, local.x - 1.0, local.y - 1.0));
+
+        lerp(v, x1, x2)
+    }
+
+    /// Noise value in range [-1, 1]
+    pub fn noise_3d(&self, p: Vector3<f32>) -> f32 {
+        let cell = p.map(f32::floor);
+        let local = p - cell;
+        let x = cell.x as i32 & 255;
+        let y = cell.y as i32 & 255;
+        let z = cell.z as i32 & 255;
+        let u = fade(local.x);
+        let v = fade(local.y);
+        let w = fade(local.z);
+
+        let a = self.hash(x) + y;
+        let aa = self.hash(a) + z;
+        let ab = self.hash(a + 1) + z;
+        let b = self.hash(x + 1) + y;
+        let ba = self.hash(b) + z;
+        let bb = self.hash(b + 1) + z;
+
+        let (lx, ly, lz) = (local.x, local.y, local.z);
+
+        let y1 = lerp(v,
+            lerp(u, gradient_3d(self.hash(aa), lx, ly, lz), gradient_3d(self.hash(ba), lx - 1.0, ly, lz)),
+            lerp(u, gradient_3d(self.hash(ab), lx, ly - 1.0, lz), gradient_3d(self.hash(bb), lx - 1.0, ly - 1.0, lz)),
+        );
+        let y2 = lerp(v,
+            lerp(u, gradient_3d(self.hash(aa + 1), lx, ly, lz - 1.0), gradient_3d(self.hash(ba + 1), lx - 1.0, ly, lz - 1.0)),
+            lerp(u, gradient_3d(self.hash(ab + 1), lx, ly - 1.0, lz - 1.0), gradient_3d(self.hash(bb + 1), lx - 1.0, ly - 1.0, lz - 1.0)),
+        );
+
+        lerp(w, y1, y2)
+    }
+
+    /// Fractal sum of octaves normalized back to range [-1, 1]
+    pub fn fbm_2d(&self, p: Vector2<f32>, octaves: u32) -> f32 {
+        self.fractal(octaves, |frequency| self.noise_2d(p * frequency))
+    }
+
+    pub fn fbm_3d(&self, p: Vector3<f32>, octaves: u32) -> f32 {
+        self.fractal(octaves, |frequency| self.noise_3d(p * frequency))
+    }
+
+    /// Fractal sum of inverted absolute noise which forms sharp crests, in range [0, 1]
+    pub fn ridged_2d(&self, p: Vector2<f32>, octaves: u32) -> f32 {
+        self.fractal(octaves, |frequency| {
+            let ridge = 1.0 - self.noise_2d(p * frequency).abs();
+            ridge * ridge
+        })
+    }
+
+    fn fractal(&self, octaves: u32, octave: impl Fn(f32) -> f32) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut total_amplitude = 0.0;
+        let mut frequency = 1.0;
+
+        for _ in 0..octaves.clamp(1, MAX_OCTAVES) {
+            sum += octave(frequency) * amplitude;
+            total_amplitude += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+
+        sum / total_amplitude
+    }
+
+    fn hash(&self, i: i32) -> i32 {
+        self.permutation[i as usize] as i32
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + t * (b - a)
+}
+
+fn gradient_2d(hash: i32, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+fn gradient_3d(hash: i32, x: f32, y: f32, z: f32) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_generates_same_terrain() {
+        let terrain = |seed| Terrain { seed, cave_threshold: Some(0.2), ..Terrain::default() }.generate(4).unwrap();
+
+        assert_eq!(terrain(3).descriptors, terrain(3).descriptors);
+        assert_ne!(terrain(3).descriptors, terrain(4).descriptors);
+    }
+
+    #[test]
+    fn columns_are_filled_from_the_bottom_within_the_heights() {
+        for shape in [TerrainShape::Fbm, TerrainShape::Ridged] {
+            let terrain = Terrain { seed: 5, min_height: 3.0, max_height: 11.0, shape, ..Terrain::default() };
+            let octree = terrain.generate(4).unwrap();
+            let origin = octree.bounds.min();
+            let mut heights = Vec::new();
+
+            for x in 0..16 {
+                for y in 0..16 {
+                    let filled = |z: u32| octree.contains(origin + Vector3::new(x, y, z).cast::<f32>());
+                    let height = (0..16).take_while(|z| filled(*z)).count();
+
+                    assert!((3..=11).contains(&height));
+                    assert!((height as u32..16).all(|z| !filled(z)));
+                    heights.push(height);
+                }
+            }
+
+            assert!(heights.iter().any(|height| *height != heights[0]));
+        }
+    }
+
+    #[test]
+    fn heights_are_cut_off_at_the_top() {
+        let octree = Terrain { min_height: 20.0, max_height: 30.0, ..Terrain::default() }.generate(3).unwrap();
+
+        assert_eq!(octree.voxels().len(), 8 * 8 * 8);
+    }
+
+    #[test]
+    fn terrain_fails_when_octree_is_full() {
+        assert!(Terrain { max_height: 30.0, ..Terrain::default() }.generate(5).is_err());
+    }
+
+    #[test]
+    fn octaves_past_the_limit_add_nothing() {
+        let noise = GradientNoise::new(7);
+        let (p2, p3) = (Vector2::new(3.7, -1.2), Vector3::new(0.3, 5.1, -2.4));
+
+        assert_eq!(noise.fbm_2d(p2, 100_000_000), noise.fbm_2d(p2, MAX_OCTAVES));
+        assert_eq!(noise.ridged_2d(p2, u32::MAX), noise.ridged_2d(p2, MAX_OCTAVES));
+        assert_eq!(noise.fbm_3d(p3, 1000), noise.fbm_3d(p3, MAX_OCTAVES));
+        assert_eq!(noise.fbm_2d(p2, 0), noise.fbm_2d(p2, 1));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,178 @@
+use std::{
+    fs::File,
+    path::Path,
+    io::{
+        self,
+        Read,
+        BufReader
+    },
+};
+use nalgebra::Vector3;
+use super::{
+    format::{
+        read_u32,
+        invalid_data
+    },
+    world::{
+        World,
+        MAX_OCTREES,
+        MAX_CHUNK_DEPTH
+    },
+};
+
+// MagicaVoxel file is the magic and version followed by the MAIN chunk. Every chunk is its id,
+// size of its content, size of its children, the content and then the children. Models are
+// children of MAIN stored as a SIZE chunk followed by an XYZI chunk, which holds the voxel count
+// and the x, y, z and color index byte of every voxel. Colors, materials and the scene graph
+// aren't supported, so every model keeps the coordinates it has in its own chunk.
+const MAGIC: &[u8; 4] = b"VOX ";
+
+// Models are at most 256 voxels along each axis, which a single chunk this deep covers
+const MAX_MODEL_DEPTH: u32 = 8;
+
+/// Voxels of every model in a MagicaVoxel file
+pub fn load(path: &Path) -> io::Result<Vec<Vector3<f32>>> {
+    read(&mut BufReader::new(File::open(path)?))
+}
+
+/// World holding the voxels in its only layer. Chunks are made deeper until the models span few
+/// enough of them, since chunks which can hold every voxel of them can't reach far.
+pub fn world(voxels: &[Vector3<f32>]) -> io::Result<World> {
+    (MAX_CHUNK_DEPTH..=MAX_MODEL_DEPTH)
+        .find_map(|depth| {
+            let mut world = World::new(depth);
+
+            voxels.iter().all(|pos| world.insert(0, *pos)).then_some(world)
+        })
+        .ok_or_else(|| invalid_data(&format!("model doesn't fit into the {} chunks of a scene", MAX_OCTREES)))
+}
+
+fn read(file: &mut impl Read) -> io::Result<Vec<Vector3<f32>>> {
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)?;
+
+    if &magic != MAGIC {
+        return Err(invalid_data("not a MagicaVoxel file"));
+    }
+
+    let _version = read_u32(file)?;
+    let (id, content_size, _) = read_chunk_header(file)?;
+
+    if &id != b"MAIN" {
+        return Err(invalid_data("MagicaVoxel file doesn't start with the MAIN chunk"));
+    }
+
+    skip(file, content_size as u64)?;
+
+    let mut voxels = Vec::new();
+
+    // Children of MAIN take the rest of the file
+    while let Some((id, content_size, children_size)) = read_next_chunk_header(file)? {
+        if &id != b"XYZI" {
+            skip(file, content_size as u64 + children_size as u64)?;
+            continue;
+        }
+
+        let count = read_u32(file)?;
+
+        if content_size as u64 != 4 + 4 * count as u64 {
+            return Err(invalid_data("XYZI chunk size doesn't match its voxel count"));
+        }
+
+        for _ in 0..count {
+            let mut voxel = [0; 4];
+            file.read_exact(&mut voxel)?;
+            voxels.push(Vector3::new(voxel[0], voxel[1], voxel[2]).cast::<f32>());
+        }
+
+        skip(file, children_size as u64)?;
+    }
+
+    Ok(voxels)
+}
+
+fn read_chunk_header(file: &mut impl Read) -> io::Result<([u8; 4], u32, u32)> {
+    let mut id = [0; 4];
+    file.read_exact(&mut id)?;
+
+    Ok((id, read_u32(file)?, read_u32(file)?))
+}
+
+// None at the end of the file
+fn read_next_chunk_header(file: &mut impl Read) -> io::Result<Option<([u8; 4], u32, u32)>> {
+    match read_chunk_header(file) {
+        Ok(header) => Ok(Some(header)),
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+fn skip(file: &mut impl Read, size: u64) -> io::Result<()> {
+    match io::copy(&mut file.take(size), &mut io::sink())? {
+        skipped if skipped == size => Ok(()),
+        _ => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &[u8; 4], content: &[u8], children: &[u8]) -> Vec<u8> {
+        [id, &(content.len() as u32).to_le_bytes()[..], &(children.len() as u32).to_le_bytes(), content, children].concat()
+    }
+
+    // File holding the chunks as the children of MAIN
+    fn file(chunks: &[Vec<u8>]) -> Vec<u8> {
+        [MAGIC, &150u32.to_le_bytes()[..], &chunk(b"MAIN", &[], &chunks.concat())].concat()
+    }
+
+    fn size(x: u32, y: u32, z: u32) -> Vec<u8> {
+        chunk(b"SIZE", &[x, y, z].map(u32::to_le_bytes).concat(), &[])
+    }
+
+    fn xyzi(voxels: &[[u8; 3]]) -> Vec<u8> {
+        let content = voxels.iter().flat_map(|[x, y, z]| [*x, *y, *z, 1]).collect::<Vec<_>>();
+
+        chunk(b"XYZI", &[&(voxels.len() as u32).to_le_bytes()[..], &content].concat(), &[])
+    }
+
+    #[test]
+    fn read_skips_unknown_chunks() {
+        let bytes = file(&[
+            size(4, 4, 4),
+            chunk(b"nTRN", &[7; 12], &chunk(b"nSHP", &[1; 5], &[])),
+            xyzi(&[[0, 0, 0], [3, 1, 2]]),
+            chunk(b"RGBA", &[255; 1024], &[]),
+        ]);
+
+        let voxels = read(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(voxels, [Vector3::new(0.0, 0.0, 0.0), Vector3::new(3.0, 1.0, 2.0)]);
+    }
+
+    #[test]
+    fn read_rejects_xyzi_size_mismatch() {
+        let mut voxels = xyzi(&[[0, 0, 0]]);
+        voxels[12] = 2; // Voxel count
+
+        let bytes = file(&[size(1, 1, 1), voxels]);
+
+        assert_eq!(read(&mut bytes.as_slice()).unwrap_err().to_string(), "XYZI chunk size doesn't match its voxel count");
+        assert!(read(&mut b"RIFF".as_slice()).is_err());
+    }
+
+    #[test]
+    fn world_fits_models_spanning_many_chunks() {
+        // 80x80x40 model with a voxel in 75 chunks of the shallowest depth
+        let voxels = (0..3)
+            .flat_map(|z| (0..5).flat_map(move |y| (0..5).map(move |x| Vector3::new(x, y, z).cast::<f32>() * 16.0 + Vector3::repeat(3.0))))
+            .collect::<Vec<_>>();
+
+        let world = world(&voxels).unwrap();
+
+        assert!(world.chunk_depth() > MAX_CHUNK_DEPTH);
+        assert_eq!(world.voxels(0).count(), 75);
+        assert!(voxels.iter().all(|pos| world.contains(0, *pos)));
+    }
+}
\ No newline at end of file
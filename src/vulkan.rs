@@ -11,18 +11,21 @@ mod image;
 mod semaphores;
 mod fences;
 
+pub use self::debug_messenger::Validation;
+
 use ash::vk;
-use std::mem::size_of;
-use nalgebra::{
-    Vector3, 
-    Vector4
+use std::{
+    mem::size_of,
+    time::Instant,
+    vec,
 };
+use nalgebra::Vector4;
 use winit::{
     event::{
-        ElementState, 
+        DeviceEvent,
         Event, 
         KeyboardInput, 
-        VirtualKeyCode, 
+        MouseScrollDelta,
         WindowEvent, 
     },
     event_loop::{
@@ -35,15 +38,20 @@ use winit::{
     }, 
 };
 use {
-    super::octree::Octree,
+    super::world::WorldBuffer,
     super::cursor::Cursor,
-    super::mouse::{
-        Mouse, 
-        MouseState
+    super::selection::SelectionState,
+    super::mouse::MouseState,
+    super::camera::CameraProjection,
+    super::input::Input,
+    super::editor::{
+        self,
+        Editor,
+        APP_NAME
     },
-    super::camera::{
-        Camera, 
-        CameraProjection
+    super::recording::{
+        Recorder,
+        Record
     },
 };
 
@@ -72,11 +80,13 @@ use self::{
     }, 
 };
 
+const PIXELS_PER_LINE: f32 = 20.0; // Touchpads scroll by pixels instead of lines
+
 pub struct App {
     window: Window,
     event_loop: Option<EventLoop<()>>,
     instance: ash::Instance,
-    debug_messenger: DebugMessenger,
+    debug_messenger: Option<DebugMessenger>,
     surface: Surface,
     device: Device,
     swapchain: Swapchain,
@@ -90,26 +100,32 @@ pub struct App {
     world_buffer: LocalBuffer,
     cursor_buffer: LocalBuffer,
     debug_buffer: DebugBuffer,
+    selection_buffer: DebugBuffer,
     raytrace_output_image: Image,
+    editor: Editor,
+    recorder: Option<Recorder>,
+    replay: Option<vec::IntoIter<(f64, Record)>>, // Records left to replay
+    swapchain_outdated: bool, // Window was resized or presenting reported the swapchain out of date
 }
 
 impl App {
-    pub fn new() -> Self {
-        let app_name = "Voxel editor";
+    /// Window starts with the window size of the editor, events the editor handles are written to the recorder
+    /// and recorded events are replayed before the editor takes input from the window
+    pub fn new(editor: Editor, recorder: Option<Recorder>, replay: Option<Vec<(f64, Record)>>, validation: Validation) -> Self {
         let entry = ash::Entry::linked();
 
         let event_loop = EventLoop::new();
         let window = WindowBuilder::new()
-            .with_title(app_name)
-            .with_inner_size(winit::dpi::LogicalSize::new(1280, 720))
+            .with_title(APP_NAME)
+            .with_inner_size(winit::dpi::LogicalSize::new(editor.window_size().width, editor.window_size().height))
             .build(&event_loop)
             .expect("Window error");
 
-        let instance = ash::Instance::new(&entry, &window, app_name);
-        let debug_messenger = DebugMessenger::new(&entry, &instance);
+        let instance = ash::Instance::new(&entry, &window, APP_NAME, validation != Validation::Off);
+        let debug_messenger = DebugMessenger::new(&entry, &instance, validation);
         let surface = Surface::new(&entry, &instance, &window);
         let device = Device::new(&instance, &surface);
-        let swapchain = Swapchain::new(&instance, &device, &surface);
+        let swapchain = Swapchain::new(&instance, &device, &surface, &window);
         let semaphores = Semaphores::new(&device, &swapchain);
         let fences = Fences::new(&device, &swapchain);
         let command_buffers = command_buffers::CommandBuffers::new(&device, &swapchain);
@@ -132,11 +148,12 @@ impl App {
             include_bytes!("../shaders/spv/edit.spv"), 
             size_of::<MouseState>()  as u32
         );
-        let world_buffer = LocalBuffer::new(&instance, &device, &descriptor_set, size_of::<Octree>() as u64, 0);
+        let world_buffer = LocalBuffer::new(&instance, &device, &descriptor_set, size_of::<WorldBuffer>() as u64, 0);
         let cursor_buffer = LocalBuffer::new(&instance, &device, &descriptor_set, size_of::<Cursor>() as u64, 1);
-        let raytrace_output_image = Image::new(&instance, &window, &device, &descriptor_set, 2);
+        let raytrace_output_image = Image::new(&instance, swapchain.extent(), &device, &descriptor_set, 2);
 
         let debug_buffer = DebugBuffer::new(&instance, &device, &descriptor_set, 16 as u64, 3);
+        let selection_buffer = DebugBuffer::new(&instance, &device, &descriptor_set, size_of::<SelectionState>() as u64, 4);
 
         Self {
             event_loop: Some(event_loop),
@@ -156,19 +173,16 @@ impl App {
             world_buffer,
             cursor_buffer,
             debug_buffer,
+            selection_buffer,
             raytrace_output_image,
+            editor,
+            recorder,
+            replay: replay.map(Vec::into_iter),
+            swapchain_outdated: false,
         }
     }
 
-    pub fn prepare(&self, octree: Octree) {
-        let staging_buffer = StagingBuffer::new(
-            &self.instance, 
-            &self.device, 
-            size_of::<Octree>() as u64,
-        );
-
-        staging_buffer.write(&self.device, &octree);
-
+    pub fn prepare(&self) {
         let init_pipeline = Pipeline::new(
             &self.device, 
             &self.descriptor_set, 
@@ -178,9 +192,18 @@ impl App {
 
         let command_buffer = self.command_buffers[0];
         
-        // Transition image layouts
         command_buffer.begin(&self.device);
+        self.transition_image_layouts(command_buffer);
+        command_buffer.bind_pipeline(&self.device, &init_pipeline);
+        command_buffer.end(&self.device);
+        command_buffer.submit_single_time(&self.device);
 
+        self.upload_world();
+        self.update_title();
+    }
+
+    // Swapchain and output images start in undefined layouts
+    fn transition_image_layouts(&self, command_buffer: vk::CommandBuffer) {
         for image in self.swapchain.present_images() {
             command_buffer.pipeline_barrier(
                 &self.device, 
@@ -199,28 +222,135 @@ impl App {
             (vk::AccessFlags::empty(), vk::AccessFlags::empty()),
             (vk::PipelineStageFlags::TOP_OF_PIPE,  vk::PipelineStageFlags::TOP_OF_PIPE),
         );
+    }
+
+    // Everything sized by the window or by the amount of swapchain images is created again,
+    // a minimized window has no size, so it keeps the old ones until it's restored
+    fn recreate_swapchain(&mut self) {
+        let size = self.window.inner_size();
+
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+
+        unsafe { self.device.device_wait_idle().unwrap() };
+
+        self.fences.destroy_fences(&self.device);
+        self.semaphores.destroy_semaphore(&self.device);
+        self.command_buffers.destroy_command_buffer(&self.device);
+        self.raytrace_output_image.destroy_image(&self.device);
+        self.swapchain.destroy_swapchain(&self.device);
+
+        self.swapchain = Swapchain::new(&self.instance, &self.device, &self.surface, &self.window);
+        self.semaphores = Semaphores::new(&self.device, &self.swapchain);
+        self.fences = Fences::new(&self.device, &self.swapchain);
+        self.command_buffers = CommandBuffers::new(&self.device, &self.swapchain);
+        self.raytrace_output_image = Image::new(&self.instance, self.swapchain.extent(), &self.device, &self.descriptor_set, 2);
+
+        let command_buffer = self.command_buffers[0];
+
+        command_buffer.begin(&self.device);
+        self.transition_image_layouts(command_buffer);
+        command_buffer.end(&self.device);
+        command_buffer.submit_single_time(&self.device);
+
+        self.swapchain_outdated = false;
+    }
+
+    // Replaces the world buffer with the CPU copy of the world
+    fn upload_world(&self) {
+        let staging_buffer = StagingBuffer::new(
+            &self.instance, 
+            &self.device, 
+            size_of::<WorldBuffer>() as u64,
+        );
+
+        staging_buffer.write(&self.device, &*self.editor.world().buffer());
+
+        // Frames in flight might still be reading the world buffer
+        unsafe { self.device.device_wait_idle().unwrap() };
+
+        let command_buffer = self.command_buffers[0];
+
+        command_buffer.begin(&self.device);
         command_buffer.copy_buffer(
             &self.device, 
             staging_buffer.buffer(), 
             self.world_buffer.buffer(), 
-            size_of::<Octree>() as u64
+            size_of::<WorldBuffer>() as u64
         );
-        command_buffer.bind_pipeline(&self.device, &init_pipeline);
-
         command_buffer.end(&self.device);
         command_buffer.submit_single_time(&self.device);
 
         staging_buffer.destroy_buffer(&self.device);
     }
 
+    fn update_title(&self) {
+        self.window.set_title(&self.editor.title());
+    }
+
+    // Editor handles the event and the window catches up with it, returns true when the editor quits
+    fn handle(&mut self, time: f64, event: editor::Event) -> bool {
+        self.record(time, &Record::Event(event));
+        self.editor.handle(event);
+
+        let changes = self.editor.take_changes();
+
+        if changes.world {
+            self.upload_world();
+        }
+        if changes.title {
+            self.update_title();
+        }
+
+        changes.quit
+    }
+
+    fn record(&mut self, time: f64, record: &Record) {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(error) = recorder.write(time, record) {
+                eprintln!("Couldn't record, stopped recording: {}", error);
+                self.recorder = None;
+            }
+        }
+    }
+
+    // Handles the recorded events up to the next frame, returns the time of the frame or none when the replay ended
+    fn replay_frame(&mut self) -> Option<f64> {
+        while let Some((time, record)) = self.replay.as_mut()?.next() {
+            match record {
+                Record::Frame => return Some(time),
+                Record::Event(event) => {
+                    // Window is made the recorded size, but only the recorded size reaches the editor
+                    if let editor::Event::Resized(size) = event {
+                        self.window.set_inner_size(size);
+                    }
+
+                    self.handle(time, event);
+                },
+            }
+        }
+
+        println!("Replay ended");
+        self.replay = None;
+        None
+    }
+
     fn render(&mut self, camera: CameraProjection, mouse: MouseState) {
-        let (present_image, image_index) = self.swapchain.acquire_next_image(&self.semaphores);
+        let Some((present_image, image_index)) = self.swapchain.acquire_next_image(&self.semaphores) else {
+            self.swapchain_outdated = true;
+            return;
+        };
+        let extent = self.swapchain.extent();
         let fence = self.fences[image_index];
         let semaphore = self.semaphores[image_index];
         let command_buffer = self.command_buffers[image_index];
-        let push_constant = (camera, mouse);
 
         fence.wait(&self.device);
+
+        self.selection_buffer.write(&self.device, &SelectionState::new(self.editor.selection()));
+
+        let push_constant = (camera, mouse);
         
         command_buffer.begin(&self.device);
         
@@ -239,8 +369,8 @@ impl App {
         command_buffer.push_constants(&self.device, &push_constant, &self.render_pipeline);
         command_buffer.dispatch(
             &self.device, 
-            self.window.inner_size().width / 16,
-            self.window.inner_size().height / 16, 
+            extent.width.div_ceil(16),
+            extent.height.div_ceil(16), 
             1
         );
 
@@ -255,7 +385,7 @@ impl App {
             &self.device, 
             (self.raytrace_output_image.image(), vk::ImageLayout::TRANSFER_SRC_OPTIMAL),
             (present_image,  vk::ImageLayout::TRANSFER_DST_OPTIMAL),
-            vk::Extent3D { width: self.window.inner_size().width, height: self.window.inner_size().height, depth: 1 },
+            vk::Extent3D { width: extent.width, height: extent.height, depth: 1 },
         );
         command_buffer.pipeline_barrier(
             &self.device, 
@@ -275,44 +405,98 @@ impl App {
             fence,
         );
 
-        self.swapchain.present_frame(
-            &self.device, 
-            image_index as u32, 
-            semaphore.render_complete(), 
-        );
+        if self.swapchain.present_frame(&self.device, image_index as u32, semaphore.render_complete()) {
+            self.swapchain_outdated = true;
+        }
 
-        if push_constant.1.left_button == ash::vk::TRUE {
+        if push_constant.1.left_button == ash::vk::TRUE || push_constant.1.right_button == ash::vk::TRUE {
             fence.wait(&self.device);
-            self.debug_buffer.read_vector(&self.device);
+
+            let edited_voxel = self.debug_buffer.read_vector(&self.device);
+            self.editor.mirror_edit(edited_voxel);
         }
     }
 
     pub fn run(mut self) {
-        let mut mouse = Mouse::default();
-        let mut camera = Camera::new(
-            Vector3::new(12.0, 0.0, 11.0), // Look from
-            Vector3::new(12.0, 12.0, 9.0)  // Look at
-        );
+        let mut start = Instant::now();
+        let mut last_frame = 0.0;
+
+        // Replays start with the recorded window size instead
+        if self.replay.is_none() {
+            self.handle(0.0, editor::Event::Resized(self.window.inner_size()));
+        }
 
         let event_loop = self.event_loop.take().unwrap();
         event_loop.run(move |event, _, control_flow| {
             *control_flow = ControlFlow::Poll;
-            match event {
-                Event::MainEventsCleared => self.render(camera.projection(), mouse.state()),
+            let event = match event {
+                Event::MainEventsCleared => {
+                    // Replay runs a recorded frame every frame, moving the camera by the recorded time between them
+                    let frame_time = match self.replay.is_some() {
+                        true => self.replay_frame(),
+                        false => Some(start.elapsed().as_secs_f64()),
+                    };
+
+                    let Some(frame_time) = frame_time else {
+                        // Live frames after a replay count from its end
+                        start = Instant::now();
+                        last_frame = 0.0;
+                        return;
+                    };
+
+                    self.record(frame_time, &Record::Frame);
+
+                    let (projection, mouse) = self.editor.frame((frame_time - last_frame) as f32);
+                    last_frame = frame_time;
+
+                    if self.swapchain_outdated {
+                        self.recreate_swapchain();
+                    }
+
+                    // Nothing can be shown while the window is minimized
+                    if self.swapchain_outdated {
+                        return;
+                    }
+
+                    self.render(projection, mouse);
+                    None
+                },
+                Event::DeviceEvent {
+                    event: DeviceEvent::MouseMotion { 
+                        delta: (x, y),
+                    },
+                    ..
+                } => Some(editor::Event::MouseMotion(x as f32, y as f32)),
                 Event::WindowEvent {
                     event, 
                     ..
                 } => match event {
-                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                    WindowEvent::CloseRequested => {
+                        *control_flow = ControlFlow::Exit;
+                        None
+                    },
+                    WindowEvent::Resized(size) => {
+                        self.swapchain_outdated = true;
+                        Some(editor::Event::Resized(size))
+                    },
                     WindowEvent::MouseInput { 
                         button,
                         state,
                         ..
-                    } => mouse.process_input(button, state),
+                    } => Some(editor::Event::Input(Input::Mouse(button), state)),
+                    WindowEvent::MouseWheel { 
+                        delta,
+                        ..
+                    } => Some(editor::Event::Scroll(match delta {
+                        MouseScrollDelta::LineDelta(_, lines) => lines,
+                        MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
+                    })),
+                    WindowEvent::ModifiersChanged(state) => Some(editor::Event::Modifiers(state)),
+                    WindowEvent::ReceivedCharacter(character) => Some(editor::Event::Character(character)),
                     WindowEvent::CursorMoved {
                         position,
                         ..
-                    } => mouse.process_movement(position, &self.window),
+                    } => Some(editor::Event::CursorMoved(position)),
                     WindowEvent::KeyboardInput  {
                         input: KeyboardInput {
                             virtual_keycode: Some(key),
@@ -320,13 +504,17 @@ impl App {
                             ..
                         },
                         ..
-                    } => match (key, state) {
-                        (VirtualKeyCode::Escape, ElementState::Pressed) => *control_flow = ControlFlow::Exit,
-                        (key, state) => camera.process_keyboard(key, state),
-                    },
-                    _ => (),
+                    } => Some(editor::Event::Input(Input::Key(key), state)),
+                    _ => None,
                 },
-                _ => ()
+                _ => None,
+            };
+
+            // Replay drives the editor alone until it ends
+            if let Some(event) = event.filter(|_| self.replay.is_none()) {
+                if self.handle(start.elapsed().as_secs_f64(), event) {
+                    *control_flow = ControlFlow::Exit;
+                }
             }
         });
     }
@@ -341,6 +529,7 @@ impl Drop for App {
             self.semaphores.destroy_semaphore(&self.device);
             self.raytrace_output_image.destroy_image(&self.device);
             self.debug_buffer.destroy_buffer(&self.device);
+            self.selection_buffer.destroy_buffer(&self.device);
             self.world_buffer.destroy_buffer(&self.device);
             self.cursor_buffer.destroy_buffer(&self.device);
             self.render_pipeline.destroy_pipeline(&self.device);
@@ -351,7 +540,9 @@ impl Drop for App {
             self.swapchain.destroy_swapchain(&self.device);
             self.device.destroy_device(None);
             self.surface.destroy_surface();
-            self.debug_messenger.destroy_debug_messenger();
+            if let Some(debug_messenger) = &self.debug_messenger {
+                debug_messenger.destroy_debug_messenger();
+            }
             self.instance.destroy_instance(None);
         }
     }
@@ -52,7 +52,7 @@ impl DebugBuffer {
         }
     }
 
-    pub fn read_vector(&self, device: &Device) {
+    pub fn read_vector(&self, device: &Device) -> nalgebra::Vector4<f32> {
         unsafe {
             const SIZE: usize = 16; 
 
@@ -72,10 +72,8 @@ impl DebugBuffer {
             let (head, body, _tail) = bytes.align_to::<nalgebra::Vector4<f32>>();
             assert!(head.is_empty(), "Data was not aligned");
         
-            let vec4 = &body[0];
-        
-            println!("pos: ({}, {}, {})", vec4.x, vec4.y, vec4.z);
-        };
+            body[0]
+        }
     }
 
     pub fn write<T: Sized>(&self, device: &Device, data: &T) {
@@ -98,10 +96,6 @@ impl DebugBuffer {
         };
     }
 
-    pub fn buffer(&self) -> vk::Buffer {
-        self.buffer
-    }
-
     pub fn _memory(&self) -> vk::DeviceMemory {
         self.memory
     }
@@ -7,21 +7,47 @@ use std::{
     ffi::CStr
 };
 
+/// Lowest severity of validation layer messages which get printed
+#[derive(Clone, Copy, PartialEq)]
+pub enum Validation {
+    Off, // Validation layer isn't enabled at all
+    Error,
+    Warning,
+    Info,
+    Verbose,
+}
+
+impl Validation {
+    fn severity(self) -> vk::DebugUtilsMessageSeverityFlagsEXT {
+        let severities = [
+            vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
+            vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
+            vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+        ];
+
+        severities[..self as usize]
+            .iter()
+            .fold(vk::DebugUtilsMessageSeverityFlagsEXT::empty(), |flags, severity| flags | *severity)
+    }
+}
+
 pub struct  DebugMessenger {
     loader: DebugUtils,
     messenger: vk::DebugUtilsMessengerEXT,
 }
 
 impl DebugMessenger {
-    pub fn new(entry: &ash::Entry, instance: &ash::Instance) -> Self {
+    /// Returns None when validation is off, since the instance has no debug utils then
+    pub fn new(entry: &ash::Entry, instance: &ash::Instance, validation: Validation) -> Option<Self> {
+        if validation == Validation::Off {
+            return None;
+        }
+
         let loader = DebugUtils::new(&entry, &instance);
     
         let debug_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
-            .message_severity(
-                vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
-                | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
-                | vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
-            )
+            .message_severity(validation.severity())
             .message_type(
                 vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
                 | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
@@ -33,10 +59,10 @@ impl DebugMessenger {
             loader.create_debug_utils_messenger(&debug_info, None).expect("Debug messenger creation error")
         };
 
-        Self { 
+        Some(Self { 
             loader, 
             messenger,
-        }
+        })
     }
 
     pub fn destroy_debug_messenger(&self) {
@@ -31,11 +31,17 @@ impl DescriptorSet {
                 .descriptor_count(1)
                 .build();
 
+            let selection_buffer = vk::DescriptorPoolSize::builder()
+                .ty(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .build();
+
             let pool_sizes = &[
                 world_buffer,
                 cursor_buffer, 
                 raytrace_output_image,
                 debug_buffer,
+                selection_buffer,
             ];
 
             let create_info = vk::DescriptorPoolCreateInfo::builder()
@@ -75,11 +81,19 @@ impl DescriptorSet {
                 .stage_flags(vk::ShaderStageFlags::COMPUTE)
                 .build();
     
+            let selection_buffer = vk::DescriptorSetLayoutBinding::builder()
+                .binding(4)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .build();
+    
             let layout_bindings = &[
                 world_buffer,
                 cursor_buffer,
                 raytrace_output_image,
                 debug_buffer,
+                selection_buffer,
             ];
 
             let descriptor_binding_flags = {
@@ -1,5 +1,4 @@
 use ash::vk;
-use winit::window::Window;
 use std::slice;
 use super::{
     Device, 
@@ -13,12 +12,12 @@ pub struct Image {
 }
 
 impl Image {
-    pub fn new(instance: &ash::Instance, window: &Window, device: &Device, descriptor_set: &DescriptorSet, binding: u32) -> Self {
+    pub fn new(instance: &ash::Instance, extent: vk::Extent2D, device: &Device, descriptor_set: &DescriptorSet, binding: u32) -> Self {
         let image = unsafe {
             let usage = vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC;
             let extent =  vk::Extent3D { 
-                width: window.inner_size().width, 
-                height: window.inner_size().height, 
+                width: extent.width, 
+                height: extent.height, 
                 depth: 1 
             };
 
@@ -8,11 +8,11 @@ use std::ffi::CStr;
 use winit::window::Window;
 
 pub trait InstanceTrait {
-    fn new(entry: &Entry, window: &Window, app_name: &str) -> Instance;
+    fn new(entry: &Entry, window: &Window, app_name: &str, validation: bool) -> Instance;
 }
 
 impl InstanceTrait for Instance {
-    fn new(entry: &Entry, window: &Window, app_name: &str) -> Instance {
+    fn new(entry: &Entry, window: &Window, app_name: &str, validation: bool) -> Instance {
         let app_name = String::from(app_name.to_owned() + "\0");
         let app_name = CStr::from_bytes_with_nul(app_name.as_bytes()).unwrap();
 
@@ -23,15 +23,16 @@ impl InstanceTrait for Instance {
             .engine_version(0)
             .api_version(vk::API_VERSION_1_2);
 
-        let layer_names = vec![
-            CStr::from_bytes_with_nul(b"VK_LAYER_KHRONOS_validation\0").unwrap().as_ptr()
-        ];
+        let mut layer_names = Vec::new();
 
         let mut instance_extensions = ash_window::enumerate_required_extensions(window)
             .unwrap()
             .to_vec();
 
-        instance_extensions.push(DebugUtils::name().as_ptr());
+        if validation {
+            layer_names.push(CStr::from_bytes_with_nul(b"VK_LAYER_KHRONOS_validation\0").unwrap().as_ptr());
+            instance_extensions.push(DebugUtils::name().as_ptr());
+        }
 
         let create_info = vk::InstanceCreateInfo::builder()
             .application_info(&appinfo)
@@ -8,6 +8,7 @@ use super::{
     Semaphores,
 };
 use std::slice;
+use winit::window::Window;
 
 const MIN_IMAGE_COUNT: u32 = 3;
 
@@ -17,11 +18,23 @@ pub struct Swapchain {
     present_images: Vec<vk::Image>,
     present_image_views: Vec<vk::ImageView>,
     image_index: usize,
+    extent: vk::Extent2D,
 }
 
 impl Swapchain {
-    pub fn new(instance: &ash::Instance, device: &Device, surface: &Surface) -> Self {
+    pub fn new(instance: &ash::Instance, device: &Device, surface: &Surface, window: &Window) -> Self {
         let loader = khr::Swapchain::new(&instance, &device);
+        let capabilities = unsafe { surface.get_physical_device_surface_capabilities(device).unwrap() };
+
+        // Surfaces without a current extent take the size of the window
+        let extent = match capabilities.current_extent.width {
+            u32::MAX => vk::Extent2D {
+                width: window.inner_size().width.clamp(capabilities.min_image_extent.width, capabilities.max_image_extent.width),
+                height: window.inner_size().height.clamp(capabilities.min_image_extent.height, capabilities.max_image_extent.height),
+            },
+            _ => capabilities.current_extent,
+        };
+
         let swapchain = unsafe {
             let format = surface
                 .get_physical_device_surface_formats(device)
@@ -30,11 +43,6 @@ impl Swapchain {
                 .find(|format| format.format == vk::Format::B8G8R8A8_SRGB)
                 .unwrap();
             
-            let extent = surface
-                .get_physical_device_surface_capabilities(device)
-                .unwrap()
-                .current_extent;
-            
             let image_usage = vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::COLOR_ATTACHMENT;
             let queue_family_index = &device.queue_family_index();
             
@@ -95,6 +103,7 @@ impl Swapchain {
             present_images, 
             present_image_views,
             image_index: 0,
+            extent,
         }
     }
 
@@ -105,30 +114,46 @@ impl Swapchain {
         }
     }
     
-    pub fn acquire_next_image(&mut self, semaphores: &Semaphores) -> (vk::Image, usize) {
+    /// Returns None when the swapchain no longer matches the surface and has to be recreated
+    pub fn acquire_next_image(&mut self, semaphores: &Semaphores) -> Option<(vk::Image, usize)> {
         let semaphore = semaphores[self.image_index].image_available();
 
-        let image_index = unsafe { 
+        let result = unsafe { 
             self.loader.acquire_next_image(
                 self.swapchain, 
                 u64::max_value(), 
                 semaphore, 
                 vk::Fence::null()
-            ).unwrap().0 as usize
+            )
+        };
+
+        let image_index = match result {
+            Ok((image_index, _)) => image_index as usize,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => return None,
+            Err(error) => panic!("Image acquisition error: {}", error),
         };
 
         self.image_index = (image_index + 1) % self.present_images.len();
 
-        (self.present_images[image_index], image_index)
+        Some((self.present_images[image_index], image_index))
     }
 
-    pub fn present_frame(&self, device: &Device, image_index: u32, semaphore: vk::Semaphore) {
+    /// Returns true when the swapchain doesn't match the surface anymore and should be recreated
+    pub fn present_frame(&self, device: &Device, image_index: u32, semaphore: vk::Semaphore) -> bool {
         let present_info = vk::PresentInfoKHR::builder()
             .wait_semaphores(slice::from_ref(&semaphore))
             .swapchains(slice::from_ref(&self.swapchain))
             .image_indices(slice::from_ref(&image_index));
 
-        unsafe { self.loader.queue_present(device.queue(), &present_info).unwrap(); }
+        match unsafe { self.loader.queue_present(device.queue(), &present_info) } {
+            Ok(suboptimal) => suboptimal,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => true,
+            Err(error) => panic!("Present error: {}", error),
+        }
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
     }
 
     pub fn present_images(&self) -> &Vec<vk::Image> {
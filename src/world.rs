@@ -0,0 +1,499 @@
+use std::collections::HashMap;
+use nalgebra::{
+    Vector3,
+    Vector4
+};
+use super::{
+    octree::Octree,
+    camera::Bookmark,
+    scene::{
+        Scene,
+        Instance,
+        BvhNode
+    },
+};
+
+pub const MAX_OCTREES: usize = 64;
+pub const MAX_CHUNK_DEPTH: u32 = 4; // Deepest chunks which still fit into octree memory with every voxel filled
+pub const MAX_INSTANCES: usize = 64;
+pub const MAX_LAYERS: usize = 8;
+const CHUNK_TABLE_SIZE: usize = 2 * MAX_OCTREES;
+const MAX_COLORS: usize = 4096;
+const COLOR_TABLE_SIZE: usize = 2 * MAX_COLORS;
+pub const DEFAULT_COLOR: u32 = 0x33FFFF; // Color of voxels which weren't painted
+const COLOR_USED_BIT: u32 = 1 << 24;
+
+pub struct Chunk {
+    pub coordinate: Vector3<i32>,
+    pub layer: usize,
+    pub octree: Octree,
+}
+
+impl Chunk {
+    /// Converts world coordinates into the coordinates of the chunk's octree
+    pub fn to_local(&self, pos: Vector3<f32>) -> Vector3<f32> {
+        pos + self.offset()
+    }
+
+    pub fn to_world(&self, pos: Vector3<f32>) -> Vector3<f32> {
+        pos - self.offset()
+    }
+
+    fn offset(&self) -> Vector3<f32> {
+        let size = self.octree.size() as f32;
+
+        self.octree.bounds.min() - self.coordinate.cast::<f32>() * size
+    }
+}
+
+pub struct Layer {
+    pub name: String,
+    pub visible: bool,
+    pub locked: bool, // Locked layers are shown but can't be edited
+}
+
+impl Layer {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            visible: true,
+            locked: false,
+        }
+    }
+}
+
+// World is made of chunk octrees of the same depth which are created on demand.
+// Chunk with coordinate c covers [c, c + 1) * chunk size in world coordinates.
+// Every layer has chunks of its own, edits go to the active layer unless it's locked.
+// Models placed by the scene's instances share the pool of octrees with the chunks.
+// Chunks take the octrees at the start of the pool in the order they were added and models take
+// the ones at its end, so a chunk edit.comp creates has the same index as its CPU copy.
+// Voxels of the chunks have the default color unless they were painted.
+pub struct World {
+    chunk_depth: u32,
+    chunks: Vec<Chunk>,
+    lookup: HashMap<(Vector3<i32>, usize), usize>,
+    colors: HashMap<(Vector3<i32>, usize), u32>, // 0xRRGGBB of painted voxels by position and layer
+    layers: Vec<Layer>,
+    active_layer: usize,
+    scene: Scene,
+    bookmarks: Vec<Bookmark>,
+}
+
+impl World {
+    /// World with a single empty layer
+    pub fn new(chunk_depth: u32) -> Self {
+        Self::with_layers(chunk_depth, vec![Layer::new("Layer 1")])
+    }
+
+    /// Empty world with the given layers, the first one is active
+    pub fn with_layers(chunk_depth: u32, layers: Vec<Layer>) -> Self {
+        assert!(!layers.is_empty() && layers.len() <= MAX_LAYERS, "World must have between 1 and {} layers", MAX_LAYERS);
+
+        Self {
+            chunk_depth,
+            chunks: Vec::new(),
+            lookup: HashMap::new(),
+            colors: HashMap::new(),
+            layers,
+            active_layer: 0,
+            scene: Scene::default(),
+            bookmarks: Vec::new(),
+        }
+    }
+
+    /// World holding a single octree, which keeps its own coordinates as world coordinates
+    pub fn from_octree(octree: Octree) -> Self {
+        let mut world = Self::new(octree.depth);
+        world.add_chunk(Vector3::repeat(1), 0, octree);
+
+        world
+    }
+
+    pub fn chunk_size(&self) -> u32 {
+        1 << self.chunk_depth
+    }
+
+    pub fn chunk_coordinate(&self, pos: Vector3<f32>) -> Vector3<i32> {
+        let size = self.chunk_size() as f32;
+
+        pos.map(|x| (x / size).floor() as i32)
+    }
+
+    pub fn chunk_depth(&self) -> u32 {
+        self.chunk_depth
+    }
+
+    /// Most voxels a layer can hold, which is when every octree is a full chunk of it
+    pub fn capacity(&self) -> u64 {
+        MAX_OCTREES as u64 * u64::from(self.chunk_size()).pow(3)
+    }
+
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    /// Octrees of the chunks can be replaced, but their coordinates and layers must stay the same
+    pub fn chunks_mut(&mut self) -> &mut [Chunk] {
+        &mut self.chunks
+    }
+
+    pub fn chunk(&self, coordinate: Vector3<i32>, layer: usize) -> Option<&Chunk> {
+        self.lookup.get(&(coordinate, layer)).map(|index| &self.chunks[*index])
+    }
+
+    /// Adds a chunk with an existing octree of the world's chunk depth, returns false if there is
+    /// no room for it or the layer already has a chunk at the coordinate
+    pub fn add_chunk(&mut self, coordinate: Vector3<i32>, layer: usize, octree: Octree) -> bool {
+        assert!(layer < self.layers.len(), "Chunk of a layer which doesn't exist");
+        assert!(octree.depth == self.chunk_depth, "Chunk octree depth doesn't match the world");
+
+        if self.octree_count() == MAX_OCTREES || self.lookup.contains_key(&(coordinate, layer)) {
+            return false;
+        }
+
+        self.lookup.insert((coordinate, layer), self.chunks.len());
+        self.chunks.push(Chunk {
+            coordinate,
+            layer,
+            octree,
+        });
+
+        true
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn layer_mut(&mut self, index: usize) -> &mut Layer {
+        &mut self.layers[index]
+    }
+
+    /// Returns None if there is no room for another layer
+    pub fn add_layer(&mut self, layer: Layer) -> Option<usize> {
+        if self.layers.len() == MAX_LAYERS {
+            return None;
+        }
+
+        self.layers.push(layer);
+        Some(self.layers.len() - 1)
+    }
+
+    pub fn active_layer(&self) -> usize {
+        self.active_layer
+    }
+
+    pub fn set_active_layer(&mut self, index: usize) {
+        assert!(index < self.layers.len(), "Layer doesn't exist");
+
+        self.active_layer = index;
+    }
+
+    /// Active layer unless it's locked
+    pub fn editable_layer(&self) -> Option<usize> {
+        (!self.layers[self.active_layer].locked).then_some(self.active_layer)
+    }
+
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    /// Returns None if there is no room for another octree
+    pub fn add_model(&mut self, octree: Octree) -> Option<usize> {
+        (self.octree_count() < MAX_OCTREES).then(|| self.scene.add_model(octree))
+    }
+
+    /// Returns None if there is no room for another instance
+    pub fn add_instance(&mut self, instance: Instance) -> Option<usize> {
+        (self.scene.instances().len() < MAX_INSTANCES).then(|| self.scene.add_instance(instance))
+    }
+
+    pub fn bookmarks(&self) -> &[Bookmark] {
+        &self.bookmarks
+    }
+
+    pub fn find_bookmark(&self, name: &str) -> Option<&Bookmark> {
+        self.bookmarks.iter().find(|bookmark| bookmark.name == name)
+    }
+
+    /// Replaces the bookmark with the same name if there is one
+    pub fn set_bookmark(&mut self, bookmark: Bookmark) {
+        match self.bookmarks.iter_mut().find(|other| other.name == bookmark.name) {
+            Some(other) => *other = bookmark,
+            None => self.bookmarks.push(bookmark),
+        }
+    }
+
+    /// Inclusive range of chunk coordinates in use
+    pub fn chunk_range(&self) -> Option<(Vector3<i32>, Vector3<i32>)> {
+        let mut coordinates = self.chunks.iter().map(|chunk| chunk.coordinate);
+        let first = coordinates.next()?;
+
+        Some(coordinates.fold((first, first), |(lower, upper), coordinate| {
+            (lower.inf(&coordinate), upper.sup(&coordinate))
+        }))
+    }
+
+    /// Returns false if the voxel needs a new chunk and there is no room for it, or if the octree
+    /// of its chunk is full
+    pub fn insert(&mut self, layer: usize, pos: Vector3<f32>) -> bool {
+        let coordinate = self.chunk_coordinate(pos);
+
+        let index = match self.lookup.get(&(coordinate, layer)) {
+            Some(index) => *index,
+            None => {
+                if !self.add_chunk(coordinate, layer, Octree::new(self.chunk_depth, Vec::new())) {
+                    return false;
+                }
+
+                self.chunks.len() - 1
+            },
+        };
+
+        let chunk = &mut self.chunks[index];
+        let pos = chunk.to_local(pos);
+
+        chunk.octree.insert(pos)
+    }
+
+    pub fn remove(&mut self, layer: usize, pos: Vector3<f32>) {
+        self.colors.remove(&(pos.map(|x| x as i32), layer));
+
+        if let Some(index) = self.lookup.get(&(self.chunk_coordinate(pos), layer)) {
+            let chunk = &mut self.chunks[*index];
+            let pos = chunk.to_local(pos);
+            chunk.octree.remove(pos);
+        }
+    }
+
+    pub fn contains(&self, layer: usize, pos: Vector3<f32>) -> bool {
+        self.chunk(self.chunk_coordinate(pos), layer)
+            .is_some_and(|chunk| chunk.octree.contains(chunk.to_local(pos)))
+    }
+
+    /// Color of the layer's voxel as 0xRRGGBB, whether it's filled or not
+    pub fn color(&self, layer: usize, pos: Vector3<f32>) -> u32 {
+        self.colors.get(&(pos.map(|x| x as i32), layer)).copied().unwrap_or(DEFAULT_COLOR)
+    }
+
+    /// Returns false if there is no room for another painted voxel
+    pub fn set_color(&mut self, layer: usize, pos: Vector3<f32>, color: u32) -> bool {
+        let key = (pos.map(|x| x as i32), layer);
+
+        if color == DEFAULT_COLOR {
+            self.colors.remove(&key);
+        } else if self.colors.len() < MAX_COLORS || self.colors.contains_key(&key) {
+            self.colors.insert(key, color);
+        } else {
+            return false;
+        }
+
+        true
+    }
+
+    /// Positions, layers and colors of the painted voxels
+    pub fn colors(&self) -> impl Iterator<Item = (Vector3<i32>, usize, u32)> + '_ {
+        self.colors.iter().map(|((pos, layer), color)| (*pos, *layer, *color))
+    }
+
+    /// Lower corner of the lowest voxel and upper corner of the highest one in any layer or instance
+    pub fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        let mut voxels = (0..self.layers.len())
+            .flat_map(|layer| self.voxels(layer))
+            .chain(self.scene.instances().iter().flat_map(|instance| self.scene.voxels(instance)));
+        let first = voxels.next()?;
+
+        Some(voxels.fold((first, first.add_scalar(1.0)), |(lower, upper), pos| {
+            (lower.inf(&pos), upper.sup(&pos.add_scalar(1.0)))
+        }))
+    }
+
+    /// World positions of the voxels in the layer's chunks
+    pub fn voxels(&self, layer: usize) -> impl Iterator<Item = Vector3<f32>> + '_ {
+        self.chunks
+            .iter()
+            .filter(move |chunk| chunk.layer == layer)
+            .flat_map(|chunk| chunk.octree.voxels().into_iter().map(|pos| chunk.to_world(pos)))
+    }
+
+    /// World in the layout of the world buffer in the shaders
+    pub fn buffer(&self) -> Box<WorldBuffer> {
+        // Every field is a number, so zeroed memory is a valid empty world
+        let mut buffer = unsafe { Box::<WorldBuffer>::new_zeroed().assume_init() };
+
+        // Chunks are added to the table in the order edit.comp would add them
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            let mut slot = chunk_hash(chunk.coordinate, chunk.layer);
+
+            while buffer.chunk_table[slot] != 0 {
+                slot = (slot + 1) % CHUNK_TABLE_SIZE;
+            }
+
+            buffer.chunk_table[slot] = index as i32 + 1;
+            buffer.octrees[index] = chunk.octree.clone();
+            buffer.chunk_coordinates[index] = chunk.coordinate.push(chunk.layer as i32);
+        }
+
+        for (index, model) in self.scene.models().iter().enumerate() {
+            buffer.octrees[self.model_octree(index)] = model.clone();
+        }
+
+        for (index, instance) in self.scene.instances().iter().enumerate() {
+            buffer.instances[index] = InstanceData {
+                rotation: [0, 1, 2].map(|row| instance.rotation.row(row).transpose().to_homogeneous()),
+                model_center: self.scene.model_center(instance).to_homogeneous(),
+                world_center: self.scene.world_center(instance).to_homogeneous(),
+                octree: self.model_octree(instance.model) as u32,
+            };
+        }
+
+        buffer.bvh[..self.scene.bvh().len()].copy_from_slice(self.scene.bvh());
+
+        for ((pos, layer), color) in &self.colors {
+            let mut slot = color_hash(*pos, *layer);
+
+            while buffer.colors[slot] != 0 {
+                slot = (slot + 1) % COLOR_TABLE_SIZE;
+            }
+
+            buffer.color_voxels[slot] = pos.push(*layer as i32);
+            buffer.colors[slot] = color | COLOR_USED_BIT;
+        }
+
+        if let Some((lower, upper)) = self.chunk_range() {
+            buffer.lower_chunk = lower.to_homogeneous();
+            buffer.upper_chunk = upper.to_homogeneous();
+        }
+
+        buffer.octree_count = self.octree_count() as u32;
+        buffer.chunk_count = self.chunks.len() as u32;
+        buffer.chunk_depth = self.chunk_depth;
+        buffer.instance_count = self.scene.instances().len() as u32;
+        buffer.layer_count = self.layers.len() as u32;
+        buffer.active_layer = self.active_layer as u32;
+        buffer.color_count = self.colors.len() as u32;
+
+        for (index, layer) in self.layers.iter().enumerate() {
+            buffer.visible_layers |= (layer.visible as u32) << index;
+            buffer.locked_layers |= (layer.locked as u32) << index;
+        }
+
+        buffer
+    }
+
+    // Index of the model's octree in the world buffer
+    fn model_octree(&self, model: usize) -> usize {
+        MAX_OCTREES - self.scene.models().len() + model
+    }
+
+    fn octree_count(&self) -> usize {
+        self.chunks.len() + self.scene.models().len()
+    }
+}
+
+// Instance in the layout of Instance in world.glsl
+#[repr(C, align(16))]
+struct InstanceData {
+    rotation: [Vector4<i32>; 3], // Rows of the rotation from model to world coordinates
+    model_center: Vector4<f32>,
+    world_center: Vector4<f32>,
+    octree: u32,
+}
+
+#[repr(C)]
+pub struct WorldBuffer {
+    octrees: [Octree; MAX_OCTREES],
+    chunk_coordinates: [Vector4<i32>; MAX_OCTREES],
+    chunk_table: [i32; CHUNK_TABLE_SIZE],
+    lower_chunk: Vector4<i32>,
+    upper_chunk: Vector4<i32>,
+    instances: [InstanceData; MAX_INSTANCES],
+    bvh: [BvhNode; 2 * MAX_INSTANCES],
+    color_voxels: [Vector4<i32>; COLOR_TABLE_SIZE],
+    colors: [u32; COLOR_TABLE_SIZE],
+    octree_count: u32,
+    chunk_count: u32,
+    chunk_depth: u32,
+    instance_count: u32,
+    layer_count: u32,
+    active_layer: u32,
+    visible_layers: u32,
+    locked_layers: u32,
+    color_count: u32,
+}
+
+// Same as chunk_hash() in world.glsl
+fn chunk_hash(coordinate: Vector3<i32>, layer: usize) -> usize {
+    let hash = (coordinate.x as u32).wrapping_mul(73856093)
+        ^ (coordinate.y as u32).wrapping_mul(19349663)
+        ^ (coordinate.z as u32).wrapping_mul(83492791)
+        ^ (layer as u32).wrapping_mul(50331653);
+
+    hash as usize % CHUNK_TABLE_SIZE
+}
+
+// Same as color_hash() in world.glsl
+fn color_hash(pos: Vector3<i32>, layer: usize) -> usize {
+    let hash = (pos.x as u32).wrapping_mul(73856093)
+        ^ (pos.y as u32).wrapping_mul(19349663)
+        ^ (pos.z as u32).wrapping_mul(83492791)
+        ^ (layer as u32).wrapping_mul(50331653);
+
+    hash as usize % COLOR_TABLE_SIZE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Matrix3;
+
+    #[test]
+    fn deepest_chunk_fits_when_full() {
+        let mut world = World::new(MAX_CHUNK_DEPTH);
+        let size = world.chunk_size();
+
+        for (x, y, z) in (0..size).flat_map(|z| (0..size).flat_map(move |y| (0..size).map(move |x| (x, y, z)))) {
+            assert!(world.insert(0, Vector3::new(x, y, z).cast::<f32>()));
+        }
+    }
+
+    #[test]
+    fn full_chunk_rejects_voxels() {
+        let mut world = World::new(MAX_CHUNK_DEPTH + 1);
+        let mut missing = 0;
+
+        for (x, y, z) in (0..8).flat_map(|z| (0..32).flat_map(move |y| (0..32).map(move |x| (x, y, z)))) {
+            let pos = Vector3::new(x, y, z).cast::<f32>();
+
+            match world.insert(0, pos) {
+                true => assert!(world.contains(0, pos)),
+                false => {
+                    assert!(!world.contains(0, pos));
+                    missing += 1;
+                },
+            }
+        }
+
+        assert!(missing > 0);
+        assert_eq!(world.chunks().len(), 1);
+    }
+
+    #[test]
+    fn buffer_keeps_chunks_at_the_start_and_models_at_the_end() {
+        let mut world = World::new(3);
+        world.insert(0, Vector3::new(1.0, 1.0, 1.0));
+        let model = world.add_model(Octree::new(2, vec![Vector3::new(4.0, 4.0, 4.0)])).unwrap();
+        world.add_instance(Instance::new(model, Vector3::new(20, 0, 0), Matrix3::identity()));
+        world.insert(0, Vector3::new(9.0, 1.0, 1.0));
+
+        let buffer = world.buffer();
+
+        // Second chunk was added after the model, but edit.comp would still have put it right after the first
+        assert_eq!(buffer.chunk_coordinates[1], Vector4::new(1, 0, 0, 0));
+        assert_eq!(buffer.octrees[1].descriptors, world.chunks()[1].octree.descriptors);
+        assert_eq!(buffer.instances[0].octree as usize, MAX_OCTREES - 1);
+        assert_eq!(buffer.octrees[MAX_OCTREES - 1].depth, 2);
+        assert_eq!((buffer.octree_count, buffer.chunk_count), (3, 2));
+    }
+}
\ No newline at end of file